@@ -0,0 +1,15 @@
+use tracing::{info, Level};
+
+#[tokasm::main]
+async fn main() {
+    unilog::init(Level::INFO, "");
+
+    tokasm::time::set_frame_rate(30);
+    let mut frames = tokasm::time::animation_frames();
+    let mut last_frame = frames.next().await;
+    loop {
+        let frame = frames.next().await;
+        info!("{:?} since last frame", frame.duration_since(last_frame));
+        last_frame = frame;
+    }
+}
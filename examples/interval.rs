@@ -0,0 +1,19 @@
+use std::time::Duration;
+
+use tokasm::time::MissedTickBehavior;
+use tracing::{info, Level};
+
+#[tokasm::main]
+async fn main() {
+    unilog::init(Level::INFO, "");
+
+    let mut interval = tokasm::time::interval(Duration::from_millis(1000));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    tokasm::spawn(async move {
+        loop {
+            interval.tick().await;
+            info!("Tick!");
+            tokasm::time::sleep(Duration::from_millis(250)).await;
+        }
+    });
+}
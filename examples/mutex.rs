@@ -0,0 +1,23 @@
+use std::{sync::Arc, time::Duration};
+
+use shadow_clone::shadow_clone;
+use tokasm::sync::Mutex;
+use tracing::{info, Level};
+
+#[tokasm::main]
+async fn main() {
+    unilog::init(Level::INFO, "");
+
+    let count = Arc::new(Mutex::new(0));
+    for name in ["first", "second", "third"] {
+        shadow_clone!(count);
+        tokasm::spawn(async move {
+            loop {
+                let mut count = count.lock().await;
+                *count += 1;
+                info!("{} incremented the count to {}", name, *count);
+                tokasm::time::sleep(Duration::from_millis(500)).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+use tokasm::sync::watch;
+use tracing::{info, Level};
+
+#[tokasm::main]
+async fn main() {
+    unilog::init(Level::INFO, "");
+
+    let (sender, receiver) = watch::channel(0);
+    tokasm::spawn(async move {
+        loop {
+            tokasm::time::sleep(Duration::from_millis(1000)).await;
+            sender.send_modify(|value| *value += 1);
+        }
+    });
+    for name in ["first", "second"] {
+        let mut receiver = receiver.clone();
+        tokasm::spawn(async move {
+            while receiver.changed().await.is_ok() {
+                info!("{} saw {}", name, *receiver.borrow_and_update());
+            }
+        });
+    }
+}
@@ -1,23 +1,23 @@
 #[cfg(not(target_arch = "wasm32"))]
 mod native {
-    use std::sync::{atomic::AtomicU64, Arc, Once};
+    use std::sync::{atomic::AtomicU64, Arc, OnceLock};
 
     use tokio::sync::Notify;
 
     pub(crate) struct Context {
         pub(crate) runtime: tokio::runtime::Runtime,
         pub(crate) task_count: AtomicU64,
+        #[cfg(feature = "idle")]
+        pub(crate) active_count: AtomicU64,
+        #[cfg(feature = "idle")]
+        pub(crate) idle: Notify,
         pub(crate) shutdown: Arc<Notify>,
     }
 
     impl Context {
         pub(crate) fn singleton() -> &'static Context {
-            static START: Once = Once::new();
-            static mut INSTANCE: Option<Context> = None;
-            START.call_once(|| unsafe {
-                INSTANCE = Some(Context::new());
-            });
-            unsafe { &INSTANCE.as_ref().unwrap() }
+            static INSTANCE: OnceLock<Context> = OnceLock::new();
+            INSTANCE.get_or_init(Context::new)
         }
 
         fn new() -> Self {
@@ -29,6 +29,10 @@ mod native {
             Self {
                 runtime,
                 task_count: AtomicU64::new(1), // main thread counts as a task
+                #[cfg(feature = "idle")]
+                active_count: AtomicU64::new(0),
+                #[cfg(feature = "idle")]
+                idle: Notify::new(),
                 shutdown,
             }
         }
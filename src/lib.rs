@@ -1,7 +1,10 @@
 pub use tokasm_macros::main;
 
-pub mod time;
 pub mod sync;
+pub mod time;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "wasm-sim"))]
+pub mod sim;
 
 #[cfg(not(target_arch = "wasm32"))]
 mod context;
@@ -19,13 +22,249 @@ mod native {
         F: Future<Output = ()> + Send + 'static,
     {
         let context = Context::singleton();
-        context.task_count.fetch_add(1, Ordering::SeqCst);
-        context.runtime.spawn(async move {
+        // created outside the task, so that it's counted as finished even if
+        // the runtime drops the task before it runs to completion
+        let guard = TaskGuard::new();
+        let task = async move {
+            #[cfg(feature = "idle")]
+            idle::track(&guard.state, future).await;
+            #[cfg(not(feature = "idle"))]
             future.await;
+            drop(guard);
+        };
+        #[cfg(feature = "test-util")]
+        if let Some(handle) = TEST_RUNTIME.with_borrow(|handle| handle.clone()) {
+            handle.spawn(task);
+            return;
+        }
+        context.runtime.spawn(task);
+    }
+
+    #[cfg(feature = "test-util")]
+    thread_local! {
+        static TEST_RUNTIME: std::cell::RefCell<Option<tokio::runtime::Handle>> =
+            const { std::cell::RefCell::new(None) };
+    }
+
+    // until the guard is dropped, `tokasm::spawn` puts tasks on the
+    // current-thread runtime (like `#[tokio::test]`'s) this is called from
+    // instead of tokasm's own, so that `tokasm::time::pause` covers them too.
+    // those tasks are dropped along with that runtime
+    #[cfg(feature = "test-util")]
+    pub fn enter_test_runtime() -> TestRuntimeGuard {
+        let handle = tokio::runtime::Handle::try_current().ok();
+        assert!(
+            handle.as_ref().is_some_and(|handle| {
+                handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::CurrentThread
+            }),
+            "`tokasm::enter_test_runtime` must be called from a current-thread runtime"
+        );
+        TestRuntimeGuard {
+            previous: TEST_RUNTIME.replace(handle),
+            _not_send: std::marker::PhantomData,
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    pub struct TestRuntimeGuard {
+        previous: Option<tokio::runtime::Handle>,
+        // the runtime is entered on this thread only
+        _not_send: std::marker::PhantomData<*const ()>,
+    }
+
+    #[cfg(feature = "test-util")]
+    impl Drop for TestRuntimeGuard {
+        fn drop(&mut self) {
+            TEST_RUNTIME.set(self.previous.take());
+        }
+    }
+
+    struct TaskGuard {
+        #[cfg(feature = "idle")]
+        state: std::sync::Arc<idle::TaskState>,
+    }
+
+    impl TaskGuard {
+        fn new() -> Self {
+            Context::singleton()
+                .task_count
+                .fetch_add(1, Ordering::SeqCst);
+            Self {
+                #[cfg(feature = "idle")]
+                state: idle::TaskState::new(),
+            }
+        }
+    }
+
+    impl Drop for TaskGuard {
+        fn drop(&mut self) {
+            #[cfg(feature = "idle")]
+            self.state.finish();
+            let context = Context::singleton();
             if context.task_count.fetch_sub(1, Ordering::SeqCst) == 1 {
                 context.shutdown.notify_one();
             }
-        });
+        }
+    }
+
+    // tracks whether each task is scheduled or running, so that
+    // `tokasm::time::idle` can tell when the runtime has nothing to do
+    #[cfg(feature = "idle")]
+    mod idle {
+        use std::{
+            future::Future,
+            pin::pin,
+            sync::{
+                atomic::{AtomicU8, Ordering},
+                Arc, OnceLock,
+            },
+            task::{Wake, Waker},
+        };
+
+        use crate::Context;
+
+        const IDLE: u8 = 0;
+        const PARKING: u8 = 1;
+        const SCHEDULED: u8 = 2;
+        const RUNNING: u8 = 3;
+        const NOTIFIED: u8 = 4;
+        // running, and the poll's idle check has already happened
+        const CHECKED: u8 = 5;
+        const FINISHED: u8 = 6;
+
+        pub(super) async fn track<F: Future<Output = ()>>(task: &Arc<TaskState>, future: F) {
+            // both wakers are made once, so polling doesn't allocate
+            let waker = Waker::from(task.clone());
+            let check = Waker::from(Arc::new(IdleCheck(task.clone())));
+            let mut future = pin!(future);
+            std::future::poll_fn(|cx| {
+                // a spawned task is always woken through the same waker
+                task.waker.get_or_init(|| cx.waker().clone());
+                task.start_poll();
+                // `yield_now` and running out of coop budget don't wake the task
+                // until the scheduler's next tick, so only let it go idle once
+                // that tick comes without it having been woken. the order tokio
+                // wakes those deferred wakers in doesn't matter, since `idle`
+                // checks again once they have all been woken.
+                //
+                // this relies on how tokio implements `yield_now`, which isn't
+                // documented: its waker goes on the worker's defer list (rather
+                // than being woken right away), and that whole list is woken at
+                // once before the worker polls any other task. if an upgrade
+                // changes that, the tests in `tests/time.rs`'s `idle` module
+                // fail, either by `idle` resolving early or never resolving
+                let _ = pin!(tokio::task::yield_now())
+                    .poll(&mut std::task::Context::from_waker(&check));
+                let poll = future
+                    .as_mut()
+                    .poll(&mut std::task::Context::from_waker(&waker));
+                if poll.is_pending() {
+                    task.park();
+                }
+                poll
+            })
+            .await;
+        }
+
+        fn went_active() {
+            Context::singleton()
+                .active_count
+                .fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn went_idle() {
+            let context = Context::singleton();
+            if context.active_count.fetch_sub(1, Ordering::SeqCst) == 1 {
+                context.idle.notify_waiters();
+            }
+        }
+
+        pub(super) struct TaskState {
+            state: AtomicU8,
+            waker: OnceLock<Waker>,
+        }
+
+        impl TaskState {
+            pub(super) fn new() -> Arc<Self> {
+                went_active();
+                Arc::new(Self {
+                    state: AtomicU8::new(SCHEDULED),
+                    waker: OnceLock::new(),
+                })
+            }
+
+            fn update(&self, f: impl Fn(u8) -> Option<u8>) -> Option<u8> {
+                self.state
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, f)
+                    .ok()
+            }
+
+            fn start_poll(&self) {
+                if self.state.swap(RUNNING, Ordering::SeqCst) == IDLE {
+                    went_active();
+                }
+            }
+
+            fn park(&self) {
+                let previous = self.update(|state| match state {
+                    RUNNING => Some(PARKING),
+                    // the check ran during the poll, when tokio wakes deferred
+                    // wakers right away (like after `block_in_place`)
+                    CHECKED => Some(IDLE),
+                    NOTIFIED => Some(SCHEDULED),
+                    _ => None,
+                });
+                if previous == Some(CHECKED) {
+                    went_idle();
+                }
+            }
+
+            // the task finished or was dropped, in whatever state it was in
+            pub(super) fn finish(&self) {
+                if self.state.swap(FINISHED, Ordering::SeqCst) != IDLE {
+                    went_idle();
+                }
+            }
+        }
+
+        impl Wake for TaskState {
+            fn wake(self: Arc<Self>) {
+                self.wake_by_ref();
+            }
+
+            fn wake_by_ref(self: &Arc<Self>) {
+                let previous = self.update(|state| match state {
+                    IDLE | PARKING => Some(SCHEDULED),
+                    RUNNING | CHECKED => Some(NOTIFIED),
+                    _ => None,
+                });
+                if previous == Some(IDLE) {
+                    went_active();
+                }
+                if let Some(waker) = self.waker.get() {
+                    waker.wake_by_ref();
+                }
+            }
+        }
+
+        struct IdleCheck(Arc<TaskState>);
+
+        impl Wake for IdleCheck {
+            fn wake(self: Arc<Self>) {
+                self.wake_by_ref();
+            }
+
+            fn wake_by_ref(self: &Arc<Self>) {
+                let previous = self.0.update(|state| match state {
+                    PARKING => Some(IDLE),
+                    RUNNING => Some(CHECKED),
+                    _ => None,
+                });
+                if previous == Some(PARKING) {
+                    went_idle();
+                }
+            }
+        }
     }
 
     pub fn wait_until_finished() {
@@ -0,0 +1,209 @@
+// a single-threaded stand-in for the browser's event loop, so that the WASM
+// backends can run natively. spawned tasks run like microtasks, and once
+// none of them are ready the clock jumps straight to the next timer
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, VecDeque},
+    future::Future,
+    pin::{pin, Pin},
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+thread_local! {
+    static EVENT_LOOP: RefCell<EventLoop> = RefCell::new(EventLoop {
+        now: Duration::ZERO,
+        tasks: HashMap::new(),
+        next_task_id: 0,
+        ready: Arc::new(Mutex::new(VecDeque::new())),
+        timers: BTreeMap::new(),
+        next_timer_id: 0,
+    });
+}
+
+struct EventLoop {
+    now: Duration,
+    tasks: HashMap<u64, Pin<Box<dyn Future<Output = ()>>>>,
+    next_task_id: u64,
+    ready: Arc<Mutex<VecDeque<u64>>>,
+    timers: BTreeMap<(Duration, u64), Box<dyn FnOnce()>>,
+    next_timer_id: u64,
+}
+
+// the future passed to `block_on` is polled under this id
+const MAIN_TASK: u64 = u64::MAX;
+
+struct TaskWaker {
+    id: u64,
+    ready: Arc<Mutex<VecDeque<u64>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+    }
+}
+
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    EVENT_LOOP.with(|event_loop| {
+        let mut event_loop = event_loop.borrow_mut();
+        let id = event_loop.next_task_id;
+        event_loop.next_task_id += 1;
+        event_loop.tasks.insert(id, Box::pin(future));
+        event_loop.ready.lock().unwrap().push_back(id);
+    });
+}
+
+// runs the event loop until `future` completes, then drops whatever tasks
+// and timers are still pending
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct Reset;
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let (tasks, timers) = EVENT_LOOP.with(|event_loop| {
+                let mut event_loop = event_loop.borrow_mut();
+                event_loop.ready.lock().unwrap().clear();
+                (
+                    std::mem::take(&mut event_loop.tasks),
+                    std::mem::take(&mut event_loop.timers),
+                )
+            });
+            drop((tasks, timers));
+        }
+    }
+
+    let _reset = Reset;
+    let ready = EVENT_LOOP.with(|event_loop| event_loop.borrow().ready.clone());
+    let waker = |id| {
+        Waker::from(Arc::new(TaskWaker {
+            id,
+            ready: ready.clone(),
+        }))
+    };
+    let mut future = pin!(future);
+    let main_waker = waker(MAIN_TASK);
+    main_waker.wake_by_ref();
+    loop {
+        loop {
+            let Some(id) = ready.lock().unwrap().pop_front() else {
+                break;
+            };
+            if id == MAIN_TASK {
+                let poll = future.as_mut().poll(&mut Context::from_waker(&main_waker));
+                if let Poll::Ready(output) = poll {
+                    return output;
+                }
+                continue;
+            }
+            // taken out while it runs, so that it can spawn other tasks
+            let Some(mut task) =
+                EVENT_LOOP.with(|event_loop| event_loop.borrow_mut().tasks.remove(&id))
+            else {
+                continue;
+            };
+            if task
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker(id)))
+                .is_pending()
+            {
+                EVENT_LOOP.with(|event_loop| event_loop.borrow_mut().tasks.insert(id, task));
+            }
+        }
+        let callback = EVENT_LOOP.with(|event_loop| {
+            let mut event_loop = event_loop.borrow_mut();
+            let ((deadline, _), callback) = event_loop.timers.pop_first()?;
+            event_loop.now = event_loop.now.max(deadline);
+            Some(callback)
+        });
+        let Some(callback) = callback else {
+            panic!("`block_on` future can never complete: no task is ready and no timer is set");
+        };
+        callback();
+    }
+}
+
+// how many timeouts are set, to check that nothing is left behind
+pub fn pending_timers() -> usize {
+    EVENT_LOOP.with(|event_loop| event_loop.borrow().timers.len())
+}
+
+// stand-ins for the browser APIs the WASM backends are built on
+pub(crate) mod host {
+    #[cfg(feature = "test-util")]
+    use std::task::Poll;
+    use std::{
+        sync::OnceLock,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use super::EVENT_LOOP;
+
+    // milliseconds since the event loop started, like `performance.now()`
+    pub fn now() -> f64 {
+        EVENT_LOOP.with(|event_loop| event_loop.borrow().now.as_secs_f64() * 1000.)
+    }
+
+    // milliseconds since the unix epoch, like `Date.now()`. it starts at the
+    // real wall time and moves with the event loop's clock
+    pub fn date_now() -> f64 {
+        static ORIGIN: OnceLock<f64> = OnceLock::new();
+        let origin = *ORIGIN.get_or_init(|| {
+            let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            since_epoch.as_secs_f64() * 1000.
+        });
+        origin + now()
+    }
+
+    pub fn set_timeout(callback: impl FnOnce() + 'static, millis: i32) -> Timer {
+        EVENT_LOOP.with(|event_loop| {
+            let mut event_loop = event_loop.borrow_mut();
+            let deadline = event_loop.now + Duration::from_millis(millis.max(0) as u64);
+            let key = (deadline, event_loop.next_timer_id);
+            event_loop.next_timer_id += 1;
+            event_loop.timers.insert(key, Box::new(callback));
+            Timer(key)
+        })
+    }
+
+    // frames come every 16ms, about what a 60Hz display gives, and get the
+    // time they fired at like `requestAnimationFrame`'s callback does
+    pub fn request_animation_frame(callback: impl FnOnce(f64) + 'static) -> FrameRequest {
+        set_timeout(move || callback(now()), 16)
+    }
+
+    pub type FrameRequest = Timer;
+
+    // clears the timeout when dropped
+    pub struct Timer((Duration, u64));
+
+    impl Drop for Timer {
+        fn drop(&mut self) {
+            // the event loop may already be gone if this is dropped on thread exit
+            let _ =
+                EVENT_LOOP.try_with(|event_loop| event_loop.borrow_mut().timers.remove(&self.0));
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    pub async fn yield_now() {
+        let mut yielded = false;
+        std::future::poll_fn(|cx| {
+            if yielded {
+                return Poll::Ready(());
+            }
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await
+    }
+}
@@ -1,55 +1,272 @@
-#[cfg(not(target_arch = "wasm32"))]
-mod native {
-    use std::ops::{Deref, DerefMut};
+// these only rely on `Notify`, so each backend gets a copy built on its own
+macro_rules! coordination {
+    () => {
+        pub struct Barrier {
+            state: std::sync::Mutex<BarrierState>,
+            notify: Notify,
+            n: usize,
+        }
 
-    pub struct Notify(tokio::sync::Notify);
+        struct BarrierState {
+            arrived: usize,
+            generation: usize,
+        }
 
-    impl Default for Notify {
-        fn default() -> Self {
-            Notify(tokio::sync::Notify::default())
+        impl Barrier {
+            pub fn new(n: usize) -> Self {
+                Self {
+                    state: std::sync::Mutex::new(BarrierState {
+                        arrived: 0,
+                        generation: 0,
+                    }),
+                    notify: Notify::new(),
+                    // like tokio, a barrier for zero tasks behaves like one for a single task
+                    n: n.max(1),
+                }
+            }
+
+            pub async fn wait(&self) -> BarrierWaitResult {
+                let mut notified = std::pin::pin!(self.notify.notified());
+                notified.as_mut().enable();
+                let generation = {
+                    let mut state = self.state.lock().unwrap();
+                    state.arrived += 1;
+                    if state.arrived == self.n {
+                        state.arrived = 0;
+                        state.generation += 1;
+                        self.notify.notify_waiters();
+                        return BarrierWaitResult(true);
+                    }
+                    state.generation
+                };
+                // if this is dropped before the barrier releases, take the arrival back
+                let _arrival = Arrival {
+                    barrier: self,
+                    generation,
+                };
+                loop {
+                    notified.as_mut().await;
+                    if self.state.lock().unwrap().generation != generation {
+                        return BarrierWaitResult(false);
+                    }
+                    notified.set(self.notify.notified());
+                    notified.as_mut().enable();
+                }
+            }
+        }
+
+        struct Arrival<'a> {
+            barrier: &'a Barrier,
+            generation: usize,
+        }
+
+        impl<'a> Drop for Arrival<'a> {
+            fn drop(&mut self) {
+                let mut state = self.barrier.state.lock().unwrap();
+                if state.generation == self.generation {
+                    state.arrived -= 1;
+                }
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        pub struct BarrierWaitResult(bool);
+
+        impl BarrierWaitResult {
+            pub fn is_leader(&self) -> bool {
+                self.0
+            }
+        }
+
+        pub struct WaitGroup {
+            count: std::sync::Mutex<usize>,
+            notify: Notify,
+        }
+
+        impl Default for WaitGroup {
+            fn default() -> Self {
+                Self::new()
+            }
         }
-    }
+
+        impl WaitGroup {
+            pub fn new() -> Self {
+                Self {
+                    count: std::sync::Mutex::new(0),
+                    notify: Notify::new(),
+                }
+            }
+
+            pub fn add(&self, n: usize) {
+                *self.count.lock().unwrap() += n;
+            }
+
+            pub fn done(&self) {
+                let mut count = self.count.lock().unwrap();
+                *count = count
+                    .checked_sub(1)
+                    .expect("WaitGroup::done called more times than add");
+                if *count == 0 {
+                    self.notify.notify_waiters();
+                }
+            }
+
+            pub async fn wait(&self) {
+                loop {
+                    // enabled before checking, so a `done` in between isn't missed
+                    let mut notified = std::pin::pin!(self.notify.notified());
+                    notified.as_mut().enable();
+                    if *self.count.lock().unwrap() == 0 {
+                        return;
+                    }
+                    notified.await;
+                }
+            }
+        }
+    };
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::{
+        future::Future,
+        ops::{Deref, DerefMut},
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    #[derive(Default)]
+    pub struct Notify(Arc<tokio::sync::Notify>);
 
     impl Notify {
         pub fn new() -> Self {
             Self::default()
         }
 
-        pub async fn notified(&self) {
-            self.0.notified().await
+        pub fn notified(&self) -> Notified<'_> {
+            Notified(self.0.notified())
+        }
+
+        pub fn notified_owned(self: Arc<Self>) -> OwnedNotified {
+            OwnedNotified(self.0.clone().notified_owned())
         }
 
         pub fn notify_one(&self) {
             self.0.notify_one();
         }
 
+        pub fn notify_last(&self) {
+            self.0.notify_last();
+        }
+
         pub fn notify_waiters(&self) {
             self.0.notify_waiters();
         }
     }
 
-    pub struct RwLock<T: ?Sized>(tokio::sync::RwLock<T>);
+    pub struct Notified<'a>(tokio::sync::futures::Notified<'a>);
 
-    impl<T: ?Sized> RwLock<T> {
+    impl<'a> Notified<'a> {
+        pub fn enable(self: Pin<&mut Self>) -> bool {
+            self.project().enable()
+        }
+
+        fn project(self: Pin<&mut Self>) -> Pin<&mut tokio::sync::futures::Notified<'a>> {
+            // the inner future is never moved out of the wrapper
+            unsafe { self.map_unchecked_mut(|notified| &mut notified.0) }
+        }
+    }
+
+    impl<'a> Future for Notified<'a> {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.project().poll(cx)
+        }
+    }
+
+    pub struct OwnedNotified(tokio::sync::futures::OwnedNotified);
+
+    impl OwnedNotified {
+        pub fn enable(self: Pin<&mut Self>) -> bool {
+            self.project().enable()
+        }
+
+        fn project(self: Pin<&mut Self>) -> Pin<&mut tokio::sync::futures::OwnedNotified> {
+            // the inner future is never moved out of the wrapper
+            unsafe { self.map_unchecked_mut(|notified| &mut notified.0) }
+        }
+    }
+
+    impl Future for OwnedNotified {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.project().poll(cx)
+        }
+    }
+
+    pub struct Mutex<T: ?Sized>(Arc<tokio::sync::Mutex<T>>);
+
+    impl<T: Default> Default for Mutex<T> {
+        fn default() -> Self {
+            Self::new(T::default())
+        }
+    }
+
+    impl<T: ?Sized> Mutex<T> {
         pub fn new(value: T) -> Self
         where
             T: Sized,
         {
-            Self(tokio::sync::RwLock::new(value))
+            Self(Arc::new(tokio::sync::Mutex::new(value)))
         }
 
-        pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
-            RwLockWriteGuard(self.0.write().await)
+        pub async fn lock(&self) -> MutexGuard<'_, T> {
+            MutexGuard(self.0.lock().await)
         }
 
-        pub async fn read(&self) -> RwLockReadGuard<'_, T> {
-            RwLockReadGuard(self.0.read().await)
+        pub fn try_lock(&self) -> Result<MutexGuard<'_, T>, TryLockError> {
+            self.0
+                .try_lock()
+                .map(MutexGuard)
+                .map_err(|_| TryLockError(()))
+        }
+
+        pub async fn lock_owned(self: Arc<Self>) -> OwnedMutexGuard<T> {
+            OwnedMutexGuard {
+                guard: self.0.clone().lock_owned().await,
+                lock: self,
+            }
+        }
+
+        pub fn try_lock_owned(self: Arc<Self>) -> Result<OwnedMutexGuard<T>, TryLockError> {
+            let guard = self
+                .0
+                .clone()
+                .try_lock_owned()
+                .map_err(|_| TryLockError(()))?;
+            Ok(OwnedMutexGuard { guard, lock: self })
+        }
+
+        // owned guards hold on to the outer `Arc` as well, so nothing else
+        // shares the inner one while the mutex can be borrowed mutably
+        pub fn get_mut(&mut self) -> &mut T {
+            Arc::get_mut(&mut self.0).unwrap().get_mut()
+        }
+
+        pub fn into_inner(self) -> T
+        where
+            T: Sized,
+        {
+            Arc::into_inner(self.0).unwrap().into_inner()
         }
     }
 
-    pub struct RwLockWriteGuard<'a, T: ?Sized>(tokio::sync::RwLockWriteGuard<'a, T>);
+    pub struct MutexGuard<'a, T: ?Sized>(tokio::sync::MutexGuard<'a, T>);
 
-    impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
         type Target = T;
 
         fn deref(&self) -> &Self::Target {
@@ -57,437 +274,2978 @@ mod native {
         }
     }
 
-    impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
         fn deref_mut(&mut self) -> &mut Self::Target {
             &mut self.0
         }
     }
 
-    pub struct RwLockReadGuard<'a, T: ?Sized>(tokio::sync::RwLockReadGuard<'a, T>);
+    pub struct OwnedMutexGuard<T: ?Sized> {
+        guard: tokio::sync::OwnedMutexGuard<T>,
+        lock: Arc<Mutex<T>>,
+    }
+
+    impl<T: ?Sized> OwnedMutexGuard<T> {
+        pub fn mutex(&self) -> &Arc<Mutex<T>> {
+            &self.lock
+        }
+    }
 
-    impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    impl<T: ?Sized> Deref for OwnedMutexGuard<T> {
         type Target = T;
 
         fn deref(&self) -> &Self::Target {
-            &self.0
+            &self.guard
         }
     }
 
-    pub mod oneshot {
-        use std::{
-            future::Future,
-            pin::Pin,
-            task::{Context, Poll},
-        };
-
-        pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
-            let (sender, receiver) = tokio::sync::oneshot::channel();
-            (Sender(sender), Receiver(receiver))
+    impl<T: ?Sized> DerefMut for OwnedMutexGuard<T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.guard
         }
+    }
 
-        pub struct Sender<T>(tokio::sync::oneshot::Sender<T>);
+    #[derive(Debug, Eq, PartialEq, Clone)]
+    pub struct TryLockError(());
 
-        impl<T> Sender<T> {
-            pub fn send(self, value: T) -> Result<(), T> {
-                self.0.send(value)
-            }
+    impl std::fmt::Display for TryLockError {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(fmt, "operation would block")
         }
+    }
 
-        #[derive(Debug, Eq, PartialEq, Clone)]
-        pub struct RecvError(());
+    impl std::error::Error for TryLockError {}
 
-        pub struct Receiver<T>(tokio::sync::oneshot::Receiver<T>);
+    pub struct Semaphore(tokio::sync::Semaphore);
 
-        impl<T> Future for Receiver<T> {
-            type Output = Result<T, RecvError>;
+    impl Semaphore {
+        pub const MAX_PERMITS: usize = tokio::sync::Semaphore::MAX_PERMITS;
 
-            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                Future::poll(Pin::new(&mut self.0), cx)
-                    .map(|value| value.map_err(|_| RecvError(())))
-            }
+        pub fn new(permits: usize) -> Self {
+            Self(tokio::sync::Semaphore::new(permits))
         }
-    }
 
-    pub mod mpsc {
-        pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
-            let (sender, receiver) = tokio::sync::mpsc::channel(buffer);
-            (Sender(sender), Receiver(receiver))
+        pub fn available_permits(&self) -> usize {
+            self.0.available_permits()
         }
 
-        #[derive(Debug)]
-        pub struct Sender<T>(tokio::sync::mpsc::Sender<T>);
-
-        impl<T> Clone for Sender<T> {
-            fn clone(&self) -> Self {
-                Self(self.0.clone())
-            }
+        pub fn add_permits(&self, permits: usize) {
+            self.0.add_permits(permits);
         }
 
-        impl<T> Sender<T> {
-            pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
-                self.0.send(value).await.map_err(|err| SendError(err.0))
-            }
-
-            pub fn blocking_send(&self, value: T) -> Result<(), SendError<T>> {
-                tokio::task::block_in_place(|| {
-                    self.0.blocking_send(value).map_err(|err| SendError(err.0))
-                })
-            }
+        pub fn close(&self) {
+            self.0.close();
         }
 
-        #[derive(PartialEq, Eq, Clone, Copy)]
-        pub struct SendError<T>(pub T);
-
-        impl<T> std::fmt::Debug for SendError<T> {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                f.debug_struct("SendError").finish_non_exhaustive()
-            }
+        pub fn is_closed(&self) -> bool {
+            self.0.is_closed()
         }
 
-        impl<T> std::fmt::Display for SendError<T> {
-            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                write!(fmt, "channel closed")
-            }
+        pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+            self.acquire_many(1).await
         }
 
-        impl<T> std::error::Error for SendError<T> {}
-
-        #[derive(PartialEq, Eq, Clone, Copy, Debug)]
-        pub enum TryRecvError {
-            Empty,
-            Disconnected,
+        pub async fn acquire_many(
+            &self,
+            permits: u32,
+        ) -> Result<SemaphorePermit<'_>, AcquireError> {
+            self.0
+                .acquire_many(permits)
+                .await
+                .map(SemaphorePermit)
+                .map_err(|_| AcquireError(()))
         }
 
-        impl std::fmt::Display for TryRecvError {
-            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                match *self {
-                    TryRecvError::Empty => "receiving on an empty channel".fmt(fmt),
-                    TryRecvError::Disconnected => "receiving on a closed channel".fmt(fmt),
-                }
-            }
+        pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+            self.try_acquire_many(1)
         }
 
-        impl std::error::Error for TryRecvError {}
+        pub fn try_acquire_many(
+            &self,
+            permits: u32,
+        ) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+            self.0
+                .try_acquire_many(permits)
+                .map(SemaphorePermit)
+                .map_err(|err| match err {
+                    tokio::sync::TryAcquireError::Closed => TryAcquireError::Closed,
+                    tokio::sync::TryAcquireError::NoPermits => TryAcquireError::NoPermits,
+                })
+        }
 
-        #[derive(Debug)]
-        pub struct Receiver<T>(tokio::sync::mpsc::Receiver<T>);
+        pub async fn acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit, AcquireError> {
+            self.acquire_many_owned(1).await
+        }
 
-        impl<T> Unpin for Receiver<T> {}
+        pub async fn acquire_many_owned(
+            self: Arc<Self>,
+            permits: u32,
+        ) -> Result<OwnedSemaphorePermit, AcquireError> {
+            self.acquire_many(permits).await?.forget();
+            Ok(OwnedSemaphorePermit {
+                semaphore: self,
+                permits,
+            })
+        }
 
-        impl<T> Receiver<T> {
-            pub async fn recv(&mut self) -> Option<T> {
-                self.0.recv().await
-            }
+        pub fn try_acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit, TryAcquireError> {
+            self.try_acquire_many_owned(1)
+        }
 
-            pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
-                self.0.try_recv().map_err(|err| match err {
-                    tokio::sync::mpsc::error::TryRecvError::Empty => TryRecvError::Empty,
-                    tokio::sync::mpsc::error::TryRecvError::Disconnected => TryRecvError::Disconnected,
-                })
-            }
+        pub fn try_acquire_many_owned(
+            self: Arc<Self>,
+            permits: u32,
+        ) -> Result<OwnedSemaphorePermit, TryAcquireError> {
+            self.try_acquire_many(permits)?.forget();
+            Ok(OwnedSemaphorePermit {
+                semaphore: self,
+                permits,
+            })
         }
     }
-}
-#[cfg(not(target_arch = "wasm32"))]
-pub use native::*;
 
-#[cfg(target_arch = "wasm32")]
-mod wasm {
-    use std::{
-        cell::UnsafeCell,
-        collections::VecDeque,
-        ops::{Deref, DerefMut},
-        sync::Arc,
-    };
+    pub struct SemaphorePermit<'a>(tokio::sync::SemaphorePermit<'a>);
 
-    use js_sys::{Function, Promise};
-    use wasm_bindgen::JsValue;
-    use wasm_bindgen_futures::JsFuture;
+    impl<'a> SemaphorePermit<'a> {
+        pub fn forget(self) {
+            self.0.forget();
+        }
 
-    pub struct InteriorMutability<T: ?Sized> {
-        cell: UnsafeCell<T>,
+        pub fn num_permits(&self) -> usize {
+            self.0.num_permits()
+        }
     }
 
-    impl<T: ?Sized> InteriorMutability<T> {
-        pub fn new(value: T) -> Self
-        where
-            T: Sized,
-        {
-            Self {
-                cell: UnsafeCell::new(value),
-            }
-        }
+    pub struct OwnedSemaphorePermit {
+        semaphore: Arc<Semaphore>,
+        permits: u32,
     }
 
-    impl<T: ?Sized> InteriorMutability<T> {
-        pub fn as_ref(&self) -> &T {
-            unsafe { &*self.cell.get() }
+    impl OwnedSemaphorePermit {
+        pub fn forget(mut self) {
+            self.permits = 0;
         }
 
-        pub fn as_mut(&self) -> &mut T {
-            unsafe { &mut *self.cell.get() }
+        pub fn num_permits(&self) -> usize {
+            self.permits as usize
         }
-    }
 
-    #[derive(Clone)]
-    pub struct Notify {
-        permit: Arc<InteriorMutability<bool>>,
-        wait_list: Arc<InteriorMutability<VecDeque<Function>>>,
+        pub fn semaphore(&self) -> &Arc<Semaphore> {
+            &self.semaphore
+        }
     }
 
-    impl Default for Notify {
-        fn default() -> Self {
-            Notify {
-                permit: Arc::new(InteriorMutability::new(false)),
-                wait_list: Arc::new(InteriorMutability::new(VecDeque::new())),
-            }
+    impl Drop for OwnedSemaphorePermit {
+        fn drop(&mut self) {
+            self.semaphore.add_permits(self.permits as usize);
         }
     }
 
-    impl Notify {
-        pub fn new() -> Self {
-            Self::default()
-        }
+    #[derive(Debug, Eq, PartialEq, Clone)]
+    pub struct AcquireError(());
 
-        pub async fn notified(&self) {
-            if *(*self.permit).as_ref() {
-                *self.permit.as_mut() = false;
-            } else {
-                let mut notify = None;
-                let promise = Promise::new(&mut |resolve, _reject| {
-                    notify = Some(resolve);
-                });
-                self.wait_list.as_mut().push_back(notify.unwrap());
-                JsFuture::from(promise).await.unwrap();
-            }
+    impl std::fmt::Display for AcquireError {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(fmt, "semaphore closed")
         }
+    }
 
-        pub fn notify_one(&self) {
-            if let Some(notify) = self.wait_list.as_mut().pop_front() {
-                notify.call0(&JsValue::null()).unwrap();
-            } else {
-                *self.permit.as_mut() = true;
-            }
-        }
+    impl std::error::Error for AcquireError {}
 
-        pub fn notify_waiters(&self) {
-            while let Some(notify) = self.wait_list.as_mut().pop_front() {
-                notify.call0(&JsValue::null()).unwrap();
+    #[derive(Debug, Eq, PartialEq, Clone)]
+    pub enum TryAcquireError {
+        Closed,
+        NoPermits,
+    }
+
+    impl std::fmt::Display for TryAcquireError {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match *self {
+                TryAcquireError::Closed => write!(fmt, "semaphore closed"),
+                TryAcquireError::NoPermits => write!(fmt, "no permits available"),
             }
         }
     }
 
-    pub struct RwLock<T: ?Sized> {
-        writers: Arc<InteriorMutability<usize>>,
-        write_notify: Arc<Notify>,
-        readers: Arc<InteriorMutability<usize>>,
-        read_notify: Arc<Notify>,
-        value: Arc<InteriorMutability<T>>,
-    }
+    impl std::error::Error for TryAcquireError {}
+
+    pub struct RwLock<T: ?Sized>(Arc<tokio::sync::RwLock<T>>);
 
     impl<T: ?Sized> RwLock<T> {
         pub fn new(value: T) -> Self
         where
             T: Sized,
         {
-            Self {
-                writers: Arc::new(InteriorMutability::new(0)),
-                write_notify: Arc::new(Notify::new()),
-                readers: Arc::new(InteriorMutability::new(0)),
-                read_notify: Arc::new(Notify::new()),
-                value: Arc::new(InteriorMutability::new(value)),
-            }
+            Self(Arc::new(tokio::sync::RwLock::new(value)))
         }
 
         pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
-            if *self.writers.as_ref().as_ref() == 0 && *self.readers.as_ref().as_ref() == 0 {
-                *self.writers.as_mut() += 1;
-            } else {
-                *self.writers.as_mut() += 1;
-                self.write_notify.notified().await;
-            }
-            RwLockWriteGuard { lock: &self }
+            RwLockWriteGuard(self.0.write().await)
         }
 
         pub async fn read(&self) -> RwLockReadGuard<'_, T> {
-            if *self.writers.as_ref().as_ref() == 0 {
-                *self.readers.as_mut() += 1;
-            } else {
-                *self.readers.as_mut() += 1;
-                self.read_notify.notified().await;
-            }
-            RwLockReadGuard { lock: &self }
+            RwLockReadGuard(self.0.read().await)
         }
-    }
 
-    pub struct RwLockWriteGuard<'a, T: ?Sized> {
-        lock: &'a RwLock<T>,
+        pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, T>, TryLockError> {
+            self.0
+                .try_write()
+                .map(RwLockWriteGuard)
+                .map_err(|_| TryLockError(()))
+        }
+
+        pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, TryLockError> {
+            self.0
+                .try_read()
+                .map(RwLockReadGuard)
+                .map_err(|_| TryLockError(()))
+        }
+
+        pub async fn write_owned(self: Arc<Self>) -> OwnedRwLockWriteGuard<T> {
+            OwnedRwLockWriteGuard(self.0.clone().write_owned().await)
+        }
+
+        pub async fn read_owned(self: Arc<Self>) -> OwnedRwLockReadGuard<T> {
+            OwnedRwLockReadGuard(self.0.clone().read_owned().await)
+        }
+    }
+
+    pub struct RwLockWriteGuard<'a, T: ?Sized>(tokio::sync::RwLockWriteGuard<'a, T>);
+
+    impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+        pub fn map<U: ?Sized, F>(this: Self, f: F) -> RwLockMappedWriteGuard<'a, U>
+        where
+            F: FnOnce(&mut T) -> &mut U,
+        {
+            RwLockMappedWriteGuard(tokio::sync::RwLockWriteGuard::map(this.0, f))
+        }
+
+        pub fn try_map<U: ?Sized, F>(
+            this: Self,
+            f: F,
+        ) -> Result<RwLockMappedWriteGuard<'a, U>, Self>
+        where
+            F: FnOnce(&mut T) -> Option<&mut U>,
+        {
+            tokio::sync::RwLockWriteGuard::try_map(this.0, f)
+                .map(RwLockMappedWriteGuard)
+                .map_err(RwLockWriteGuard)
+        }
+
+        pub fn downgrade(self) -> RwLockReadGuard<'a, T> {
+            RwLockReadGuard(self.0.downgrade())
+        }
     }
 
-    impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
         type Target = T;
 
         fn deref(&self) -> &Self::Target {
-            self.lock.value.as_ref().as_ref()
+            &self.0
         }
     }
 
-    impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
         fn deref_mut(&mut self) -> &mut Self::Target {
-            self.lock.value.as_mut()
+            &mut self.0
         }
     }
 
-    impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
-        fn drop(&mut self) {
-            *self.lock.writers.as_mut() -= 1;
-            if *self.lock.writers.as_ref().as_ref() > 0 {
-                self.lock.write_notify.notify_one();
-            } else if *self.lock.readers.as_ref().as_ref() > 0 {
-                self.lock.read_notify.notify_waiters();
-            }
+    pub struct RwLockMappedWriteGuard<'a, T: ?Sized>(tokio::sync::RwLockMappedWriteGuard<'a, T>);
+
+    impl<'a, T: ?Sized> Deref for RwLockMappedWriteGuard<'a, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
         }
     }
 
-    pub struct RwLockReadGuard<'a, T: ?Sized> {
-        lock: &'a RwLock<T>,
+    impl<'a, T: ?Sized> DerefMut for RwLockMappedWriteGuard<'a, T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+
+    pub struct RwLockReadGuard<'a, T: ?Sized>(tokio::sync::RwLockReadGuard<'a, T>);
+
+    impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+        pub fn map<U: ?Sized, F>(this: Self, f: F) -> RwLockReadGuard<'a, U>
+        where
+            F: FnOnce(&T) -> &U,
+        {
+            RwLockReadGuard(tokio::sync::RwLockReadGuard::map(this.0, f))
+        }
+
+        pub fn try_map<U: ?Sized, F>(this: Self, f: F) -> Result<RwLockReadGuard<'a, U>, Self>
+        where
+            F: FnOnce(&T) -> Option<&U>,
+        {
+            tokio::sync::RwLockReadGuard::try_map(this.0, f)
+                .map(RwLockReadGuard)
+                .map_err(RwLockReadGuard)
+        }
+    }
+
+    impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    pub struct OwnedRwLockWriteGuard<T: ?Sized>(tokio::sync::OwnedRwLockWriteGuard<T>);
+
+    impl<T: ?Sized> OwnedRwLockWriteGuard<T> {
+        pub fn map<U: ?Sized, F>(this: Self, f: F) -> OwnedRwLockMappedWriteGuard<T, U>
+        where
+            F: FnOnce(&mut T) -> &mut U,
+        {
+            OwnedRwLockMappedWriteGuard(tokio::sync::OwnedRwLockWriteGuard::map(this.0, f))
+        }
+
+        pub fn try_map<U: ?Sized, F>(
+            this: Self,
+            f: F,
+        ) -> Result<OwnedRwLockMappedWriteGuard<T, U>, Self>
+        where
+            F: FnOnce(&mut T) -> Option<&mut U>,
+        {
+            tokio::sync::OwnedRwLockWriteGuard::try_map(this.0, f)
+                .map(OwnedRwLockMappedWriteGuard)
+                .map_err(OwnedRwLockWriteGuard)
+        }
+
+        pub fn downgrade(self) -> OwnedRwLockReadGuard<T> {
+            OwnedRwLockReadGuard(self.0.downgrade())
+        }
     }
 
-    impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    impl<T: ?Sized> Deref for OwnedRwLockWriteGuard<T> {
         type Target = T;
 
-        fn deref(&self) -> &Self::Target {
-            self.lock.value.as_ref().as_ref()
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl<T: ?Sized> DerefMut for OwnedRwLockWriteGuard<T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+
+    pub struct OwnedRwLockMappedWriteGuard<T: ?Sized, U: ?Sized = T>(
+        tokio::sync::OwnedRwLockMappedWriteGuard<T, U>,
+    );
+
+    impl<T: ?Sized, U: ?Sized> Deref for OwnedRwLockMappedWriteGuard<T, U> {
+        type Target = U;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl<T: ?Sized, U: ?Sized> DerefMut for OwnedRwLockMappedWriteGuard<T, U> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+
+    pub struct OwnedRwLockReadGuard<T: ?Sized, U: ?Sized = T>(
+        tokio::sync::OwnedRwLockReadGuard<T, U>,
+    );
+
+    impl<T: ?Sized, U: ?Sized> OwnedRwLockReadGuard<T, U> {
+        pub fn map<V: ?Sized, F>(this: Self, f: F) -> OwnedRwLockReadGuard<T, V>
+        where
+            F: FnOnce(&U) -> &V,
+        {
+            OwnedRwLockReadGuard(tokio::sync::OwnedRwLockReadGuard::map(this.0, f))
+        }
+
+        pub fn try_map<V: ?Sized, F>(this: Self, f: F) -> Result<OwnedRwLockReadGuard<T, V>, Self>
+        where
+            F: FnOnce(&U) -> Option<&V>,
+        {
+            tokio::sync::OwnedRwLockReadGuard::try_map(this.0, f)
+                .map(OwnedRwLockReadGuard)
+                .map_err(OwnedRwLockReadGuard)
+        }
+    }
+
+    impl<T: ?Sized, U: ?Sized> Deref for OwnedRwLockReadGuard<T, U> {
+        type Target = U;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    pub struct OnceCell<T>(tokio::sync::OnceCell<T>);
+
+    impl<T> Default for OnceCell<T> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<T> OnceCell<T> {
+        pub fn new() -> Self {
+            Self(tokio::sync::OnceCell::new())
+        }
+
+        pub fn get(&self) -> Option<&T> {
+            self.0.get()
+        }
+
+        pub fn initialized(&self) -> bool {
+            self.0.initialized()
+        }
+
+        pub fn set(&self, value: T) -> Result<(), SetError<T>> {
+            self.0.set(value).map_err(|err| match err {
+                tokio::sync::SetError::AlreadyInitializedError(value) => {
+                    SetError::AlreadyInitializedError(value)
+                }
+                tokio::sync::SetError::InitializingError(value) => {
+                    SetError::InitializingError(value)
+                }
+            })
+        }
+
+        pub async fn get_or_init<F, Fut>(&self, f: F) -> &T
+        where
+            F: FnOnce() -> Fut,
+            Fut: Future<Output = T>,
+        {
+            self.0.get_or_init(f).await
+        }
+
+        pub async fn get_or_try_init<E, F, Fut>(&self, f: F) -> Result<&T, E>
+        where
+            F: FnOnce() -> Fut,
+            Fut: Future<Output = Result<T, E>>,
+        {
+            self.0.get_or_try_init(f).await
+        }
+
+        pub fn into_inner(self) -> Option<T> {
+            self.0.into_inner()
+        }
+    }
+
+    #[derive(PartialEq, Eq)]
+    pub enum SetError<T> {
+        AlreadyInitializedError(T),
+        InitializingError(T),
+    }
+
+    impl<T> std::fmt::Debug for SetError<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match *self {
+                SetError::AlreadyInitializedError(..) => "AlreadyInitializedError(..)".fmt(f),
+                SetError::InitializingError(..) => "InitializingError(..)".fmt(f),
+            }
+        }
+    }
+
+    impl<T> std::fmt::Display for SetError<T> {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match *self {
+                SetError::AlreadyInitializedError(..) => "cell already initialized".fmt(fmt),
+                SetError::InitializingError(..) => "cell is being initialized".fmt(fmt),
+            }
+        }
+    }
+
+    impl<T> std::error::Error for SetError<T> {}
+
+    pub mod oneshot {
+        use std::{
+            future::Future,
+            pin::Pin,
+            task::{Context, Poll},
+        };
+
+        pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+            let (sender, receiver) = tokio::sync::oneshot::channel();
+            (Sender(sender), Receiver(receiver))
+        }
+
+        pub struct Sender<T>(tokio::sync::oneshot::Sender<T>);
+
+        impl<T> Sender<T> {
+            pub fn send(self, value: T) -> Result<(), T> {
+                self.0.send(value)
+            }
+
+            pub fn is_closed(&self) -> bool {
+                self.0.is_closed()
+            }
+
+            pub async fn closed(&mut self) {
+                self.0.closed().await
+            }
+        }
+
+        #[derive(Debug, Eq, PartialEq, Clone)]
+        pub struct RecvError(());
+
+        #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+        pub enum TryRecvError {
+            Empty,
+            Closed,
+        }
+
+        impl std::fmt::Display for TryRecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TryRecvError::Empty => "channel empty".fmt(fmt),
+                    TryRecvError::Closed => "channel closed".fmt(fmt),
+                }
+            }
+        }
+
+        impl std::error::Error for TryRecvError {}
+
+        pub struct Receiver<T>(tokio::sync::oneshot::Receiver<T>);
+
+        impl<T> Receiver<T> {
+            pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+                self.0.try_recv().map_err(|err| match err {
+                    tokio::sync::oneshot::error::TryRecvError::Empty => TryRecvError::Empty,
+                    tokio::sync::oneshot::error::TryRecvError::Closed => TryRecvError::Closed,
+                })
+            }
+
+            pub fn close(&mut self) {
+                self.0.close()
+            }
+        }
+
+        impl<T> Future for Receiver<T> {
+            type Output = Result<T, RecvError>;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                Future::poll(Pin::new(&mut self.0), cx)
+                    .map(|value| value.map_err(|_| RecvError(())))
+            }
+        }
+    }
+
+    pub mod broadcast {
+        pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+            let (sender, receiver) = tokio::sync::broadcast::channel(capacity);
+            (Sender(sender), Receiver(receiver))
+        }
+
+        #[derive(Debug)]
+        pub struct Sender<T>(tokio::sync::broadcast::Sender<T>);
+
+        impl<T> Clone for Sender<T> {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T> Sender<T> {
+            pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+                self.0.send(value).map_err(|err| SendError(err.0))
+            }
+
+            pub fn subscribe(&self) -> Receiver<T> {
+                Receiver(self.0.subscribe())
+            }
+
+            pub fn receiver_count(&self) -> usize {
+                self.0.receiver_count()
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct Receiver<T>(tokio::sync::broadcast::Receiver<T>);
+
+        impl<T: Clone> Receiver<T> {
+            pub async fn recv(&mut self) -> Result<T, RecvError> {
+                self.0.recv().await.map_err(|err| match err {
+                    tokio::sync::broadcast::error::RecvError::Closed => RecvError::Closed,
+                    tokio::sync::broadcast::error::RecvError::Lagged(skipped) => {
+                        RecvError::Lagged(skipped)
+                    }
+                })
+            }
+
+            pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+                self.0.try_recv().map_err(|err| match err {
+                    tokio::sync::broadcast::error::TryRecvError::Empty => TryRecvError::Empty,
+                    tokio::sync::broadcast::error::TryRecvError::Closed => TryRecvError::Closed,
+                    tokio::sync::broadcast::error::TryRecvError::Lagged(skipped) => {
+                        TryRecvError::Lagged(skipped)
+                    }
+                })
+            }
+
+            pub fn resubscribe(&self) -> Self {
+                Self(self.0.resubscribe())
+            }
+        }
+
+        #[derive(PartialEq, Eq, Clone, Copy)]
+        pub struct SendError<T>(pub T);
+
+        impl<T> std::fmt::Debug for SendError<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("SendError").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> std::fmt::Display for SendError<T> {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(fmt, "channel closed")
+            }
+        }
+
+        impl<T> std::error::Error for SendError<T> {}
+
+        #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+        pub enum RecvError {
+            Closed,
+            Lagged(u64),
+        }
+
+        impl std::fmt::Display for RecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    RecvError::Closed => write!(fmt, "channel closed"),
+                    RecvError::Lagged(skipped) => write!(fmt, "channel lagged by {}", skipped),
+                }
+            }
+        }
+
+        impl std::error::Error for RecvError {}
+
+        #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+        pub enum TryRecvError {
+            Empty,
+            Closed,
+            Lagged(u64),
+        }
+
+        impl std::fmt::Display for TryRecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TryRecvError::Empty => write!(fmt, "channel empty"),
+                    TryRecvError::Closed => write!(fmt, "channel closed"),
+                    TryRecvError::Lagged(skipped) => write!(fmt, "channel lagged by {}", skipped),
+                }
+            }
+        }
+
+        impl std::error::Error for TryRecvError {}
+    }
+
+    pub mod watch {
+        use std::ops::Deref;
+
+        pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+            let (sender, receiver) = tokio::sync::watch::channel(init);
+            (Sender(sender), Receiver(receiver))
+        }
+
+        #[derive(Debug)]
+        pub struct Sender<T>(tokio::sync::watch::Sender<T>);
+
+        impl<T> Sender<T> {
+            pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+                self.0.send(value).map_err(|err| SendError(err.0))
+            }
+
+            pub fn send_modify<F>(&self, modify: F)
+            where
+                F: FnOnce(&mut T),
+            {
+                self.0.send_modify(modify);
+            }
+
+            pub fn send_replace(&self, value: T) -> T {
+                self.0.send_replace(value)
+            }
+
+            pub fn borrow(&self) -> Ref<'_, T> {
+                Ref(self.0.borrow())
+            }
+
+            pub fn subscribe(&self) -> Receiver<T> {
+                Receiver(self.0.subscribe())
+            }
+
+            pub fn receiver_count(&self) -> usize {
+                self.0.receiver_count()
+            }
+
+            pub fn is_closed(&self) -> bool {
+                self.0.is_closed()
+            }
+
+            pub async fn closed(&self) {
+                self.0.closed().await;
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct Receiver<T>(tokio::sync::watch::Receiver<T>);
+
+        impl<T> Clone for Receiver<T> {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T> Receiver<T> {
+            pub async fn changed(&mut self) -> Result<(), RecvError> {
+                self.0.changed().await.map_err(|_| RecvError(()))
+            }
+
+            pub fn has_changed(&self) -> Result<bool, RecvError> {
+                self.0.has_changed().map_err(|_| RecvError(()))
+            }
+
+            pub fn borrow(&self) -> Ref<'_, T> {
+                Ref(self.0.borrow())
+            }
+
+            pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+                Ref(self.0.borrow_and_update())
+            }
+        }
+
+        pub struct Ref<'a, T>(tokio::sync::watch::Ref<'a, T>);
+
+        impl<'a, T> Deref for Ref<'a, T> {
+            type Target = T;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        #[derive(PartialEq, Eq, Clone, Copy)]
+        pub struct SendError<T>(pub T);
+
+        impl<T> std::fmt::Debug for SendError<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("SendError").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> std::fmt::Display for SendError<T> {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(fmt, "channel closed")
+            }
+        }
+
+        impl<T> std::error::Error for SendError<T> {}
+
+        #[derive(Debug, Eq, PartialEq, Clone)]
+        pub struct RecvError(());
+
+        impl std::fmt::Display for RecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(fmt, "channel closed")
+            }
+        }
+
+        impl std::error::Error for RecvError {}
+    }
+
+    pub mod mpsc {
+        use std::{
+            pin::Pin,
+            task::{Context, Poll},
+        };
+
+        use futures_core::Stream;
+
+        pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
+            let (sender, receiver) = tokio::sync::mpsc::channel(buffer);
+            (Sender(sender), Receiver(receiver))
+        }
+
+        #[derive(Debug)]
+        pub struct Sender<T>(tokio::sync::mpsc::Sender<T>);
+
+        impl<T> Clone for Sender<T> {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T> Sender<T> {
+            pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+                self.0.send(value).await.map_err(|err| SendError(err.0))
+            }
+
+            pub fn blocking_send(&self, value: T) -> Result<(), SendError<T>> {
+                tokio::task::block_in_place(|| {
+                    self.0.blocking_send(value).map_err(|err| SendError(err.0))
+                })
+            }
+
+            pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+                self.0.try_send(value).map_err(|err| match err {
+                    tokio::sync::mpsc::error::TrySendError::Full(value) => {
+                        TrySendError::Full(value)
+                    }
+                    tokio::sync::mpsc::error::TrySendError::Closed(value) => {
+                        TrySendError::Closed(value)
+                    }
+                })
+            }
+
+            pub async fn reserve(&self) -> Result<Permit<'_, T>, SendError<()>> {
+                self.0
+                    .reserve()
+                    .await
+                    .map(Permit)
+                    .map_err(|_| SendError(()))
+            }
+
+            pub fn capacity(&self) -> usize {
+                self.0.capacity()
+            }
+
+            pub fn max_capacity(&self) -> usize {
+                self.0.max_capacity()
+            }
+
+            pub async fn closed(&self) {
+                self.0.closed().await
+            }
+
+            pub fn is_closed(&self) -> bool {
+                self.0.is_closed()
+            }
+
+            pub fn same_channel(&self, other: &Self) -> bool {
+                self.0.same_channel(&other.0)
+            }
+
+            pub fn downgrade(&self) -> WeakSender<T> {
+                WeakSender(self.0.downgrade())
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct WeakSender<T>(tokio::sync::mpsc::WeakSender<T>);
+
+        impl<T> Clone for WeakSender<T> {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T> WeakSender<T> {
+            pub fn upgrade(&self) -> Option<Sender<T>> {
+                self.0.upgrade().map(Sender)
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct Permit<'a, T>(tokio::sync::mpsc::Permit<'a, T>);
+
+        impl<'a, T> Permit<'a, T> {
+            pub fn send(self, value: T) {
+                self.0.send(value)
+            }
+        }
+
+        pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+            let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+            (UnboundedSender(sender), UnboundedReceiver(receiver))
+        }
+
+        #[derive(Debug)]
+        pub struct UnboundedSender<T>(tokio::sync::mpsc::UnboundedSender<T>);
+
+        impl<T> Clone for UnboundedSender<T> {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T> UnboundedSender<T> {
+            pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+                self.0.send(value).map_err(|err| SendError(err.0))
+            }
+
+            pub async fn closed(&self) {
+                self.0.closed().await
+            }
+
+            pub fn is_closed(&self) -> bool {
+                self.0.is_closed()
+            }
+
+            pub fn same_channel(&self, other: &Self) -> bool {
+                self.0.same_channel(&other.0)
+            }
+
+            pub fn downgrade(&self) -> WeakUnboundedSender<T> {
+                WeakUnboundedSender(self.0.downgrade())
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct WeakUnboundedSender<T>(tokio::sync::mpsc::WeakUnboundedSender<T>);
+
+        impl<T> Clone for WeakUnboundedSender<T> {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T> WeakUnboundedSender<T> {
+            pub fn upgrade(&self) -> Option<UnboundedSender<T>> {
+                self.0.upgrade().map(UnboundedSender)
+            }
+        }
+
+        #[derive(PartialEq, Eq, Clone, Copy)]
+        pub struct SendError<T>(pub T);
+
+        impl<T> std::fmt::Debug for SendError<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("SendError").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> std::fmt::Display for SendError<T> {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(fmt, "channel closed")
+            }
+        }
+
+        impl<T> std::error::Error for SendError<T> {}
+
+        #[derive(PartialEq, Eq, Clone, Copy)]
+        pub enum TrySendError<T> {
+            Full(T),
+            Closed(T),
+        }
+
+        impl<T> std::fmt::Debug for TrySendError<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TrySendError::Full(..) => "Full(..)".fmt(f),
+                    TrySendError::Closed(..) => "Closed(..)".fmt(f),
+                }
+            }
+        }
+
+        impl<T> std::fmt::Display for TrySendError<T> {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TrySendError::Full(..) => "no available capacity".fmt(fmt),
+                    TrySendError::Closed(..) => "channel closed".fmt(fmt),
+                }
+            }
+        }
+
+        impl<T> std::error::Error for TrySendError<T> {}
+
+        impl<T> From<SendError<T>> for TrySendError<T> {
+            fn from(err: SendError<T>) -> Self {
+                TrySendError::Closed(err.0)
+            }
+        }
+
+        #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+        pub enum TryRecvError {
+            Empty,
+            Disconnected,
+        }
+
+        impl std::fmt::Display for TryRecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TryRecvError::Empty => "receiving on an empty channel".fmt(fmt),
+                    TryRecvError::Disconnected => "receiving on a closed channel".fmt(fmt),
+                }
+            }
+        }
+
+        impl std::error::Error for TryRecvError {}
+
+        #[derive(Debug)]
+        pub struct Receiver<T>(tokio::sync::mpsc::Receiver<T>);
+
+        impl<T> Unpin for Receiver<T> {}
+
+        impl<T> Receiver<T> {
+            pub async fn recv(&mut self) -> Option<T> {
+                self.0.recv().await
+            }
+
+            pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+                self.0.try_recv().map_err(|err| match err {
+                    tokio::sync::mpsc::error::TryRecvError::Empty => TryRecvError::Empty,
+                    tokio::sync::mpsc::error::TryRecvError::Disconnected => TryRecvError::Disconnected,
+                })
+            }
+
+            pub async fn recv_many(&mut self, buffer: &mut Vec<T>, limit: usize) -> usize {
+                self.0.recv_many(buffer, limit).await
+            }
+
+            pub fn close(&mut self) {
+                self.0.close()
+            }
+
+            pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+                self.0.poll_recv(cx)
+            }
+        }
+
+        impl<T> Stream for Receiver<T> {
+            type Item = T;
+
+            fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+                self.poll_recv(cx)
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct UnboundedReceiver<T>(tokio::sync::mpsc::UnboundedReceiver<T>);
+
+        impl<T> Unpin for UnboundedReceiver<T> {}
+
+        impl<T> UnboundedReceiver<T> {
+            pub async fn recv(&mut self) -> Option<T> {
+                self.0.recv().await
+            }
+
+            pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+                self.0.try_recv().map_err(|err| match err {
+                    tokio::sync::mpsc::error::TryRecvError::Empty => TryRecvError::Empty,
+                    tokio::sync::mpsc::error::TryRecvError::Disconnected => TryRecvError::Disconnected,
+                })
+            }
+
+            pub async fn recv_many(&mut self, buffer: &mut Vec<T>, limit: usize) -> usize {
+                self.0.recv_many(buffer, limit).await
+            }
+
+            pub fn close(&mut self) {
+                self.0.close()
+            }
+
+            pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+                self.0.poll_recv(cx)
+            }
+        }
+
+        impl<T> Stream for UnboundedReceiver<T> {
+            type Item = T;
+
+            fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+                self.poll_recv(cx)
+            }
+        }
+    }
+
+    coordination!();
+}
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+
+// nothing in here touches JS, so the `wasm-sim` feature also builds it natively
+// (exposed as `sync::sim`) to test it side by side with the tokio wrappers
+#[cfg(any(target_arch = "wasm32", feature = "wasm-sim"))]
+mod wasm {
+    use std::{
+        cell::{Cell, RefCell, UnsafeCell},
+        collections::VecDeque,
+        future::Future,
+        marker::{PhantomData, PhantomPinned},
+        mem::ManuallyDrop,
+        ops::{Deref, DerefMut},
+        pin::Pin,
+        rc::Rc,
+        sync::Arc,
+        task::{Context, Poll, Waker},
+    };
+
+    // everything here is single-threaded, so shared state lives in `Rc`s and
+    // cells, and `Arc` only shows up where the API matches tokio's
+    #[derive(Clone)]
+    pub struct Notify {
+        permit: Rc<Cell<bool>>,
+        notify_waiters_calls: Rc<Cell<usize>>,
+        wait_list: Rc<RefCell<VecDeque<Rc<RefCell<NotifyWaiter>>>>>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Notification {
+        One,
+        Last,
+        All,
+    }
+
+    struct NotifyWaiter {
+        notified: Option<Notification>,
+        waker: Option<Waker>,
+    }
+
+    impl NotifyWaiter {
+        // wakes after letting go of the waiter, in case the waker polls right away
+        fn notify(waiter: &RefCell<NotifyWaiter>, notification: Notification) {
+            let waker = {
+                let mut waiter = waiter.borrow_mut();
+                waiter.notified = Some(notification);
+                waiter.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    // shared by `Notified` and `OwnedNotified`, which only differ in how they
+    // hold on to the `Notify`
+    struct NotifiedState {
+        // like tokio, a `notify_waiters` call after creation counts even if
+        // the future was never polled
+        notify_waiters_calls: usize,
+        waiter: Option<Rc<RefCell<NotifyWaiter>>>,
+        done: bool,
+    }
+
+    impl NotifiedState {
+        fn new(notify: &Notify) -> Self {
+            Self {
+                notify_waiters_calls: notify.notify_waiters_calls.get(),
+                waiter: None,
+                done: false,
+            }
+        }
+
+        fn poll(&mut self, notify: &Notify, waker: Option<&Waker>) -> Poll<()> {
+            if self.done {
+                return Poll::Ready(());
+            }
+            match &self.waiter {
+                Some(waiter) => {
+                    let mut waiter = waiter.borrow_mut();
+                    if waiter.notified.is_none() {
+                        if let Some(waker) = waker {
+                            waiter.waker = Some(waker.clone());
+                        }
+                        return Poll::Pending;
+                    }
+                    drop(waiter);
+                    self.waiter = None;
+                }
+                None if notify.notify_waiters_calls.get() != self.notify_waiters_calls => {}
+                None if notify.permit.get() => {
+                    notify.permit.set(false);
+                }
+                None => {
+                    let waiter = Rc::new(RefCell::new(NotifyWaiter {
+                        notified: None,
+                        waker: waker.cloned(),
+                    }));
+                    notify.wait_list.borrow_mut().push_back(waiter.clone());
+                    self.waiter = Some(waiter);
+                    return Poll::Pending;
+                }
+            }
+            self.done = true;
+            Poll::Ready(())
+        }
+
+        // claims a notification that arrived but was never observed, so the
+        // caller can act on it instead of having it passed along on drop
+        fn take_notification(&mut self) -> bool {
+            match self.waiter.take() {
+                Some(waiter) if waiter.borrow().notified.is_some() => true,
+                waiter => {
+                    self.waiter = waiter;
+                    false
+                }
+            }
+        }
+
+        fn cancel(&mut self, notify: &Notify) {
+            let Some(waiter) = self.waiter.take() else {
+                return;
+            };
+            let notified = waiter.borrow().notified;
+            match notified {
+                None => notify
+                    .wait_list
+                    .borrow_mut()
+                    .retain(|other| !Rc::ptr_eq(other, &waiter)),
+                // a notification meant for this waiter goes to the next one instead
+                Some(Notification::One) => notify.notify_one(),
+                Some(Notification::Last) => notify.notify_last(),
+                Some(Notification::All) => {}
+            }
+        }
+    }
+
+    // `!Unpin` like tokio's, so code that compiles here also compiles natively
+    pub struct Notified<'a> {
+        notify: &'a Notify,
+        state: NotifiedState,
+        _pinned: PhantomPinned,
+    }
+
+    impl<'a> Notified<'a> {
+        pub fn enable(self: Pin<&mut Self>) -> bool {
+            // nothing is moved out of the future
+            let this = unsafe { self.get_unchecked_mut() };
+            this.state.poll(this.notify, None).is_ready()
+        }
+    }
+
+    impl<'a> Future for Notified<'a> {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let this = unsafe { self.get_unchecked_mut() };
+            this.state.poll(this.notify, Some(cx.waker()))
+        }
+    }
+
+    impl<'a> Drop for Notified<'a> {
+        fn drop(&mut self) {
+            self.state.cancel(self.notify);
+        }
+    }
+
+    pub struct OwnedNotified {
+        notify: Arc<Notify>,
+        state: NotifiedState,
+        _pinned: PhantomPinned,
+    }
+
+    impl OwnedNotified {
+        pub fn enable(self: Pin<&mut Self>) -> bool {
+            let this = unsafe { self.get_unchecked_mut() };
+            this.state.poll(&this.notify, None).is_ready()
+        }
+    }
+
+    impl Future for OwnedNotified {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let this = unsafe { self.get_unchecked_mut() };
+            this.state.poll(&this.notify, Some(cx.waker()))
+        }
+    }
+
+    impl Drop for OwnedNotified {
+        fn drop(&mut self) {
+            self.state.cancel(&self.notify);
+        }
+    }
+
+    // awaits a notification, and if dropped before it completes, calls
+    // `on_cancel` with whether a notification had already been handed over
+    struct Waiting<'a, F: FnOnce(bool) + Unpin> {
+        notified: Notified<'a>,
+        on_cancel: Option<F>,
+    }
+
+    impl<'a, F: FnOnce(bool) + Unpin> Waiting<'a, F> {
+        fn new(notify: &'a Notify, on_cancel: F) -> Self {
+            Self {
+                notified: notify.notified(),
+                on_cancel: Some(on_cancel),
+            }
+        }
+    }
+
+    impl<'a, F: FnOnce(bool) + Unpin> Future for Waiting<'a, F> {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            // `notified` is never moved out of `Waiting`
+            let this = unsafe { self.get_unchecked_mut() };
+            std::task::ready!(unsafe { Pin::new_unchecked(&mut this.notified) }.poll(cx));
+            this.on_cancel = None;
+            Poll::Ready(())
+        }
+    }
+
+    impl<'a, F: FnOnce(bool) + Unpin> Drop for Waiting<'a, F> {
+        fn drop(&mut self) {
+            if let Some(on_cancel) = self.on_cancel.take() {
+                on_cancel(self.notified.state.take_notification());
+            }
+        }
+    }
+
+    impl Default for Notify {
+        fn default() -> Self {
+            Notify {
+                permit: Rc::new(Cell::new(false)),
+                notify_waiters_calls: Rc::new(Cell::new(0)),
+                wait_list: Rc::new(RefCell::new(VecDeque::new())),
+            }
+        }
+    }
+
+    impl Notify {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn notified(&self) -> Notified<'_> {
+            Notified {
+                notify: self,
+                state: NotifiedState::new(self),
+                _pinned: PhantomPinned,
+            }
+        }
+
+        pub fn notified_owned(self: Arc<Self>) -> OwnedNotified {
+            OwnedNotified {
+                state: NotifiedState::new(&self),
+                notify: self,
+                _pinned: PhantomPinned,
+            }
+        }
+
+        pub fn notify_one(&self) {
+            let waiter = self.wait_list.borrow_mut().pop_front();
+            match waiter {
+                Some(waiter) => NotifyWaiter::notify(&waiter, Notification::One),
+                None => self.permit.set(true),
+            }
+        }
+
+        pub fn notify_last(&self) {
+            let waiter = self.wait_list.borrow_mut().pop_back();
+            match waiter {
+                Some(waiter) => NotifyWaiter::notify(&waiter, Notification::Last),
+                None => self.permit.set(true),
+            }
+        }
+
+        pub fn notify_waiters(&self) {
+            self.notify_waiters_calls
+                .set(self.notify_waiters_calls.get() + 1);
+            let waiters = std::mem::take(&mut *self.wait_list.borrow_mut());
+            for waiter in waiters {
+                NotifyWaiter::notify(&waiter, Notification::All);
+            }
+        }
+    }
+
+    // waiters are queued on `notify`, which wakes them in FIFO order, and the
+    // lock is handed directly to the next waiter when a guard is dropped
+    pub struct Mutex<T: ?Sized> {
+        locked: Cell<bool>,
+        waiters: Cell<usize>,
+        notify: Notify,
+        value: UnsafeCell<T>,
+    }
+
+    impl<T: Default> Default for Mutex<T> {
+        fn default() -> Self {
+            Self::new(T::default())
+        }
+    }
+
+    impl<T: ?Sized> Mutex<T> {
+        pub fn new(value: T) -> Self
+        where
+            T: Sized,
+        {
+            Self {
+                locked: Cell::new(false),
+                waiters: Cell::new(0),
+                notify: Notify::new(),
+                value: UnsafeCell::new(value),
+            }
+        }
+
+        pub async fn lock(&self) -> MutexGuard<'_, T> {
+            if self.locked.get() {
+                self.waiters.set(self.waiters.get() + 1);
+                Waiting::new(&self.notify, |handed_off| {
+                    if handed_off {
+                        drop(MutexGuard { lock: self });
+                    } else {
+                        self.waiters.set(self.waiters.get() - 1);
+                    }
+                })
+                .await;
+            } else {
+                self.locked.set(true);
+            }
+            MutexGuard { lock: self }
+        }
+
+        pub fn try_lock(&self) -> Result<MutexGuard<'_, T>, TryLockError> {
+            if self.locked.get() {
+                Err(TryLockError(()))
+            } else {
+                self.locked.set(true);
+                Ok(MutexGuard { lock: self })
+            }
+        }
+
+        pub async fn lock_owned(self: Arc<Self>) -> OwnedMutexGuard<T> {
+            std::mem::forget(self.lock().await);
+            OwnedMutexGuard { lock: self }
+        }
+
+        pub fn try_lock_owned(self: Arc<Self>) -> Result<OwnedMutexGuard<T>, TryLockError> {
+            std::mem::forget(self.try_lock()?);
+            Ok(OwnedMutexGuard { lock: self })
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            self.value.get_mut()
+        }
+
+        pub fn into_inner(self) -> T
+        where
+            T: Sized,
+        {
+            self.value.into_inner()
+        }
+
+        fn unlock(&self) {
+            if self.waiters.get() > 0 {
+                self.waiters.set(self.waiters.get() - 1);
+                self.notify.notify_one();
+            } else {
+                self.locked.set(false);
+            }
+        }
+    }
+
+    pub struct MutexGuard<'a, T: ?Sized> {
+        lock: &'a Mutex<T>,
+    }
+
+    impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            // the guard is the only way to the value while the lock is held
+            unsafe { &*self.lock.value.get() }
+        }
+    }
+
+    impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            unsafe { &mut *self.lock.value.get() }
+        }
+    }
+
+    impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
+        fn drop(&mut self) {
+            self.lock.unlock();
+        }
+    }
+
+    pub struct OwnedMutexGuard<T: ?Sized> {
+        lock: Arc<Mutex<T>>,
+    }
+
+    impl<T: ?Sized> OwnedMutexGuard<T> {
+        pub fn mutex(&self) -> &Arc<Mutex<T>> {
+            &self.lock
+        }
+    }
+
+    impl<T: ?Sized> Deref for OwnedMutexGuard<T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            unsafe { &*self.lock.value.get() }
+        }
+    }
+
+    impl<T: ?Sized> DerefMut for OwnedMutexGuard<T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            unsafe { &mut *self.lock.value.get() }
+        }
+    }
+
+    impl<T: ?Sized> Drop for OwnedMutexGuard<T> {
+        fn drop(&mut self) {
+            self.lock.unlock();
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq, Clone)]
+    pub struct TryLockError(());
+
+    impl std::fmt::Display for TryLockError {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(fmt, "operation would block")
+        }
+    }
+
+    impl std::error::Error for TryLockError {}
+
+    // waiters are queued in FIFO order and released permits are handed to the
+    // front of the queue first, so a large request is never starved by
+    // smaller ones arriving after it
+    pub struct Semaphore {
+        permits: Cell<usize>,
+        closed: Cell<bool>,
+        wait_list: RefCell<VecDeque<Rc<RefCell<SemaphoreWaiter>>>>,
+    }
+
+    struct SemaphoreWaiter {
+        remaining: usize,
+        waker: Option<Waker>,
+    }
+
+    impl SemaphoreWaiter {
+        fn wake(waiter: &RefCell<SemaphoreWaiter>) {
+            let waker = waiter.borrow_mut().waker.take();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    // a queued `acquire_many`, which gives back whatever it was assigned if it
+    // stops waiting, whether it was closed or dropped
+    struct Acquire<'a> {
+        semaphore: &'a Semaphore,
+        waiter: Option<Rc<RefCell<SemaphoreWaiter>>>,
+        needed: usize,
+    }
+
+    impl<'a> Future for Acquire<'a> {
+        type Output = Result<(), AcquireError>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let Some(waiter) = self.waiter.clone() else {
+                return Poll::Ready(Ok(()));
+            };
+            let remaining = waiter.borrow().remaining;
+            if remaining == 0 {
+                self.waiter = None;
+                Poll::Ready(Ok(()))
+            } else if self.semaphore.closed.get() {
+                drop(self.waiter.take());
+                self.release(&waiter);
+                Poll::Ready(Err(AcquireError(())))
+            } else {
+                waiter.borrow_mut().waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    impl<'a> Acquire<'a> {
+        fn release(&self, waiter: &Rc<RefCell<SemaphoreWaiter>>) {
+            self.semaphore
+                .wait_list
+                .borrow_mut()
+                .retain(|other| !Rc::ptr_eq(other, waiter));
+            let remaining = waiter.borrow().remaining;
+            self.semaphore.add_permits(self.needed - remaining);
+        }
+    }
+
+    impl<'a> Drop for Acquire<'a> {
+        fn drop(&mut self) {
+            if let Some(waiter) = self.waiter.take() {
+                self.release(&waiter);
+            }
+        }
+    }
+
+    impl Semaphore {
+        pub const MAX_PERMITS: usize = usize::MAX >> 3;
+
+        pub fn new(permits: usize) -> Self {
+            assert!(
+                permits <= Self::MAX_PERMITS,
+                "a semaphore may not have more than MAX_PERMITS permits ({})",
+                Self::MAX_PERMITS
+            );
+            Self {
+                permits: Cell::new(permits),
+                closed: Cell::new(false),
+                wait_list: RefCell::new(VecDeque::new()),
+            }
+        }
+
+        pub fn available_permits(&self) -> usize {
+            self.permits.get()
+        }
+
+        pub fn add_permits(&self, mut permits: usize) {
+            while permits > 0 {
+                let Some(waiter) = self.wait_list.borrow().front().cloned() else {
+                    break;
+                };
+                let remaining = {
+                    let mut waiter = waiter.borrow_mut();
+                    let assigned = permits.min(waiter.remaining);
+                    waiter.remaining -= assigned;
+                    permits -= assigned;
+                    waiter.remaining
+                };
+                if remaining == 0 {
+                    self.wait_list.borrow_mut().pop_front();
+                    SemaphoreWaiter::wake(&waiter);
+                }
+            }
+            // like tokio, only what is left after serving the waiters counts
+            // towards the limit
+            assert!(
+                permits <= Self::MAX_PERMITS,
+                "cannot add more than MAX_PERMITS permits ({})",
+                Self::MAX_PERMITS
+            );
+            let available = self.permits.get();
+            assert!(
+                available + permits <= Self::MAX_PERMITS,
+                "number of added permits ({}) would overflow MAX_PERMITS ({})",
+                permits,
+                Self::MAX_PERMITS
+            );
+            self.permits.set(available + permits);
+        }
+
+        pub fn close(&self) {
+            self.closed.set(true);
+            let waiters = std::mem::take(&mut *self.wait_list.borrow_mut());
+            for waiter in waiters {
+                SemaphoreWaiter::wake(&waiter);
+            }
+        }
+
+        pub fn is_closed(&self) -> bool {
+            self.closed.get()
+        }
+
+        pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+            self.acquire_many(1).await
+        }
+
+        pub async fn acquire_many(
+            &self,
+            permits: u32,
+        ) -> Result<SemaphorePermit<'_>, AcquireError> {
+            if self.closed.get() {
+                return Err(AcquireError(()));
+            }
+            let needed = permits as usize;
+            if self.permits.get() >= needed {
+                self.permits.set(self.permits.get() - needed);
+            } else {
+                // permits are only ever available while nobody is waiting, so
+                // take what's there and queue up for the rest
+                let assigned = self.permits.take();
+                let waiter = Rc::new(RefCell::new(SemaphoreWaiter {
+                    remaining: needed - assigned,
+                    waker: None,
+                }));
+                self.wait_list.borrow_mut().push_back(waiter.clone());
+                Acquire {
+                    semaphore: self,
+                    waiter: Some(waiter),
+                    needed,
+                }
+                .await?;
+            }
+            Ok(SemaphorePermit {
+                semaphore: self,
+                permits,
+            })
+        }
+
+        pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+            self.try_acquire_many(1)
+        }
+
+        pub fn try_acquire_many(
+            &self,
+            permits: u32,
+        ) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+            if self.closed.get() {
+                Err(TryAcquireError::Closed)
+            } else if self.permits.get() >= permits as usize {
+                self.permits.set(self.permits.get() - permits as usize);
+                Ok(SemaphorePermit {
+                    semaphore: self,
+                    permits,
+                })
+            } else {
+                Err(TryAcquireError::NoPermits)
+            }
+        }
+
+        pub async fn acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit, AcquireError> {
+            self.acquire_many_owned(1).await
+        }
+
+        pub async fn acquire_many_owned(
+            self: Arc<Self>,
+            permits: u32,
+        ) -> Result<OwnedSemaphorePermit, AcquireError> {
+            self.acquire_many(permits).await?.forget();
+            Ok(OwnedSemaphorePermit {
+                semaphore: self,
+                permits,
+            })
+        }
+
+        pub fn try_acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit, TryAcquireError> {
+            self.try_acquire_many_owned(1)
+        }
+
+        pub fn try_acquire_many_owned(
+            self: Arc<Self>,
+            permits: u32,
+        ) -> Result<OwnedSemaphorePermit, TryAcquireError> {
+            self.try_acquire_many(permits)?.forget();
+            Ok(OwnedSemaphorePermit {
+                semaphore: self,
+                permits,
+            })
+        }
+    }
+
+    pub struct SemaphorePermit<'a> {
+        semaphore: &'a Semaphore,
+        permits: u32,
+    }
+
+    impl<'a> SemaphorePermit<'a> {
+        pub fn forget(mut self) {
+            self.permits = 0;
+        }
+
+        pub fn num_permits(&self) -> usize {
+            self.permits as usize
+        }
+    }
+
+    impl<'a> Drop for SemaphorePermit<'a> {
+        fn drop(&mut self) {
+            self.semaphore.add_permits(self.permits as usize);
+        }
+    }
+
+    pub struct OwnedSemaphorePermit {
+        semaphore: Arc<Semaphore>,
+        permits: u32,
+    }
+
+    impl OwnedSemaphorePermit {
+        pub fn forget(mut self) {
+            self.permits = 0;
+        }
+
+        pub fn num_permits(&self) -> usize {
+            self.permits as usize
+        }
+
+        pub fn semaphore(&self) -> &Arc<Semaphore> {
+            &self.semaphore
+        }
+    }
+
+    impl Drop for OwnedSemaphorePermit {
+        fn drop(&mut self) {
+            self.semaphore.add_permits(self.permits as usize);
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq, Clone)]
+    pub struct AcquireError(());
+
+    impl std::fmt::Display for AcquireError {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(fmt, "semaphore closed")
+        }
+    }
+
+    impl std::error::Error for AcquireError {}
+
+    #[derive(Debug, Eq, PartialEq, Clone)]
+    pub enum TryAcquireError {
+        Closed,
+        NoPermits,
+    }
+
+    impl std::fmt::Display for TryAcquireError {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match *self {
+                TryAcquireError::Closed => write!(fmt, "semaphore closed"),
+                TryAcquireError::NoPermits => write!(fmt, "no permits available"),
+            }
+        }
+    }
+
+    impl std::error::Error for TryAcquireError {}
+
+    // like tokio, readers take one permit and writers take all of them, so the
+    // semaphore's FIFO queue makes new readers wait behind a queued writer
+    const MAX_READS: u32 = u32::MAX >> 3;
+
+    pub struct RwLock<T: ?Sized> {
+        semaphore: Semaphore,
+        value: UnsafeCell<T>,
+    }
+
+    impl<T: ?Sized> RwLock<T> {
+        pub fn new(value: T) -> Self
+        where
+            T: Sized,
+        {
+            Self {
+                semaphore: Semaphore::new(MAX_READS as usize),
+                value: UnsafeCell::new(value),
+            }
+        }
+
+        // the semaphore is never closed, so acquiring can't fail
+        pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+            self.semaphore
+                .acquire_many(MAX_READS)
+                .await
+                .unwrap()
+                .forget();
+            self.write_guard()
+        }
+
+        pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+            self.semaphore.acquire().await.unwrap().forget();
+            self.read_guard()
+        }
+
+        pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, T>, TryLockError> {
+            match self.semaphore.try_acquire_many(MAX_READS) {
+                Ok(permit) => permit.forget(),
+                Err(_) => return Err(TryLockError(())),
+            }
+            Ok(self.write_guard())
+        }
+
+        pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, TryLockError> {
+            match self.semaphore.try_acquire() {
+                Ok(permit) => permit.forget(),
+                Err(_) => return Err(TryLockError(())),
+            }
+            Ok(self.read_guard())
+        }
+
+        pub async fn write_owned(self: Arc<Self>) -> OwnedRwLockWriteGuard<T> {
+            self.semaphore
+                .acquire_many(MAX_READS)
+                .await
+                .unwrap()
+                .forget();
+            OwnedRwLockWriteGuard {
+                data: self.value.get(),
+                lock: self,
+            }
+        }
+
+        pub async fn read_owned(self: Arc<Self>) -> OwnedRwLockReadGuard<T> {
+            self.semaphore.acquire().await.unwrap().forget();
+            OwnedRwLockReadGuard {
+                data: self.value.get(),
+                lock: self,
+            }
+        }
+
+        fn write_guard(&self) -> RwLockWriteGuard<'_, T> {
+            RwLockWriteGuard {
+                semaphore: &self.semaphore,
+                data: self.value.get(),
+                marker: PhantomData,
+            }
+        }
+
+        fn read_guard(&self) -> RwLockReadGuard<'_, T> {
+            RwLockReadGuard {
+                semaphore: &self.semaphore,
+                data: self.value.get(),
+                marker: PhantomData,
+            }
+        }
+    }
+
+    // guards point straight at the (possibly mapped) data, and only hold on to
+    // the semaphore to release their permits
+    pub struct RwLockWriteGuard<'a, T: ?Sized> {
+        semaphore: &'a Semaphore,
+        data: *mut T,
+        marker: PhantomData<&'a mut T>,
+    }
+
+    impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+        pub fn map<U: ?Sized, F>(mut this: Self, f: F) -> RwLockMappedWriteGuard<'a, U>
+        where
+            F: FnOnce(&mut T) -> &mut U,
+        {
+            let data = f(&mut *this) as *mut U;
+            let this = ManuallyDrop::new(this);
+            RwLockMappedWriteGuard {
+                semaphore: this.semaphore,
+                data,
+                marker: PhantomData,
+            }
+        }
+
+        pub fn try_map<U: ?Sized, F>(
+            mut this: Self,
+            f: F,
+        ) -> Result<RwLockMappedWriteGuard<'a, U>, Self>
+        where
+            F: FnOnce(&mut T) -> Option<&mut U>,
+        {
+            let Some(data) = f(&mut *this).map(|data| data as *mut U) else {
+                return Err(this);
+            };
+            let this = ManuallyDrop::new(this);
+            Ok(RwLockMappedWriteGuard {
+                semaphore: this.semaphore,
+                data,
+                marker: PhantomData,
+            })
+        }
+
+        pub fn downgrade(self) -> RwLockReadGuard<'a, T> {
+            let this = ManuallyDrop::new(self);
+            this.semaphore.add_permits(MAX_READS as usize - 1);
+            RwLockReadGuard {
+                semaphore: this.semaphore,
+                data: this.data,
+                marker: PhantomData,
+            }
+        }
+    }
+
+    impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            unsafe { &*self.data }
+        }
+    }
+
+    impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            unsafe { &mut *self.data }
+        }
+    }
+
+    impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+        fn drop(&mut self) {
+            self.semaphore.add_permits(MAX_READS as usize);
+        }
+    }
+
+    pub struct RwLockMappedWriteGuard<'a, T: ?Sized> {
+        semaphore: &'a Semaphore,
+        data: *mut T,
+        marker: PhantomData<&'a mut T>,
+    }
+
+    impl<'a, T: ?Sized> Deref for RwLockMappedWriteGuard<'a, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            unsafe { &*self.data }
+        }
+    }
+
+    impl<'a, T: ?Sized> DerefMut for RwLockMappedWriteGuard<'a, T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            unsafe { &mut *self.data }
+        }
+    }
+
+    impl<'a, T: ?Sized> Drop for RwLockMappedWriteGuard<'a, T> {
+        fn drop(&mut self) {
+            self.semaphore.add_permits(MAX_READS as usize);
+        }
+    }
+
+    pub struct RwLockReadGuard<'a, T: ?Sized> {
+        semaphore: &'a Semaphore,
+        data: *const T,
+        marker: PhantomData<&'a T>,
+    }
+
+    impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+        pub fn map<U: ?Sized, F>(this: Self, f: F) -> RwLockReadGuard<'a, U>
+        where
+            F: FnOnce(&T) -> &U,
+        {
+            let data = f(&*this) as *const U;
+            let this = ManuallyDrop::new(this);
+            RwLockReadGuard {
+                semaphore: this.semaphore,
+                data,
+                marker: PhantomData,
+            }
+        }
+
+        pub fn try_map<U: ?Sized, F>(this: Self, f: F) -> Result<RwLockReadGuard<'a, U>, Self>
+        where
+            F: FnOnce(&T) -> Option<&U>,
+        {
+            let Some(data) = f(&*this).map(|data| data as *const U) else {
+                return Err(this);
+            };
+            let this = ManuallyDrop::new(this);
+            Ok(RwLockReadGuard {
+                semaphore: this.semaphore,
+                data,
+                marker: PhantomData,
+            })
+        }
+    }
+
+    impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            unsafe { &*self.data }
+        }
+    }
+
+    impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+        fn drop(&mut self) {
+            self.semaphore.add_permits(1);
+        }
+    }
+
+    // owned guards keep the lock alive instead, and hand the `Arc` over when
+    // they're mapped or downgraded
+    pub struct OwnedRwLockWriteGuard<T: ?Sized> {
+        lock: Arc<RwLock<T>>,
+        data: *mut T,
+    }
+
+    impl<T: ?Sized> OwnedRwLockWriteGuard<T> {
+        pub fn map<U: ?Sized, F>(mut this: Self, f: F) -> OwnedRwLockMappedWriteGuard<T, U>
+        where
+            F: FnOnce(&mut T) -> &mut U,
+        {
+            let data = f(&mut *this) as *mut U;
+            OwnedRwLockMappedWriteGuard {
+                lock: this.into_lock(),
+                data,
+            }
+        }
+
+        pub fn try_map<U: ?Sized, F>(
+            mut this: Self,
+            f: F,
+        ) -> Result<OwnedRwLockMappedWriteGuard<T, U>, Self>
+        where
+            F: FnOnce(&mut T) -> Option<&mut U>,
+        {
+            let Some(data) = f(&mut *this).map(|data| data as *mut U) else {
+                return Err(this);
+            };
+            Ok(OwnedRwLockMappedWriteGuard {
+                lock: this.into_lock(),
+                data,
+            })
+        }
+
+        pub fn downgrade(self) -> OwnedRwLockReadGuard<T> {
+            let data = self.data;
+            let lock = self.into_lock();
+            lock.semaphore.add_permits(MAX_READS as usize - 1);
+            OwnedRwLockReadGuard { lock, data }
+        }
+
+        fn into_lock(self) -> Arc<RwLock<T>> {
+            let this = ManuallyDrop::new(self);
+            unsafe { std::ptr::read(&this.lock) }
+        }
+    }
+
+    impl<T: ?Sized> Deref for OwnedRwLockWriteGuard<T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            unsafe { &*self.data }
+        }
+    }
+
+    impl<T: ?Sized> DerefMut for OwnedRwLockWriteGuard<T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            unsafe { &mut *self.data }
+        }
+    }
+
+    impl<T: ?Sized> Drop for OwnedRwLockWriteGuard<T> {
+        fn drop(&mut self) {
+            self.lock.semaphore.add_permits(MAX_READS as usize);
+        }
+    }
+
+    pub struct OwnedRwLockMappedWriteGuard<T: ?Sized, U: ?Sized = T> {
+        lock: Arc<RwLock<T>>,
+        data: *mut U,
+    }
+
+    impl<T: ?Sized, U: ?Sized> Deref for OwnedRwLockMappedWriteGuard<T, U> {
+        type Target = U;
+
+        fn deref(&self) -> &Self::Target {
+            unsafe { &*self.data }
+        }
+    }
+
+    impl<T: ?Sized, U: ?Sized> DerefMut for OwnedRwLockMappedWriteGuard<T, U> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            unsafe { &mut *self.data }
+        }
+    }
+
+    impl<T: ?Sized, U: ?Sized> Drop for OwnedRwLockMappedWriteGuard<T, U> {
+        fn drop(&mut self) {
+            self.lock.semaphore.add_permits(MAX_READS as usize);
+        }
+    }
+
+    pub struct OwnedRwLockReadGuard<T: ?Sized, U: ?Sized = T> {
+        lock: Arc<RwLock<T>>,
+        data: *const U,
+    }
+
+    impl<T: ?Sized, U: ?Sized> OwnedRwLockReadGuard<T, U> {
+        pub fn map<V: ?Sized, F>(this: Self, f: F) -> OwnedRwLockReadGuard<T, V>
+        where
+            F: FnOnce(&U) -> &V,
+        {
+            let data = f(&*this) as *const V;
+            OwnedRwLockReadGuard {
+                lock: this.into_lock(),
+                data,
+            }
+        }
+
+        pub fn try_map<V: ?Sized, F>(this: Self, f: F) -> Result<OwnedRwLockReadGuard<T, V>, Self>
+        where
+            F: FnOnce(&U) -> Option<&V>,
+        {
+            let Some(data) = f(&*this).map(|data| data as *const V) else {
+                return Err(this);
+            };
+            Ok(OwnedRwLockReadGuard {
+                lock: this.into_lock(),
+                data,
+            })
+        }
+
+        fn into_lock(self) -> Arc<RwLock<T>> {
+            let this = ManuallyDrop::new(self);
+            unsafe { std::ptr::read(&this.lock) }
+        }
+    }
+
+    impl<T: ?Sized, U: ?Sized> Deref for OwnedRwLockReadGuard<T, U> {
+        type Target = U;
+
+        fn deref(&self) -> &Self::Target {
+            unsafe { &*self.data }
+        }
+    }
+
+    impl<T: ?Sized, U: ?Sized> Drop for OwnedRwLockReadGuard<T, U> {
+        fn drop(&mut self) {
+            self.lock.semaphore.add_permits(1);
+        }
+    }
+
+    // initializers queue on a single permit, so they run one at a time, and the
+    // semaphore is closed once the value is set to release everyone still waiting
+    pub struct OnceCell<T> {
+        value: std::cell::OnceCell<T>,
+        semaphore: Semaphore,
+    }
+
+    impl<T> Default for OnceCell<T> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<T> OnceCell<T> {
+        pub fn new() -> Self {
+            Self {
+                value: std::cell::OnceCell::new(),
+                semaphore: Semaphore::new(1),
+            }
+        }
+
+        pub fn get(&self) -> Option<&T> {
+            self.value.get()
+        }
+
+        pub fn initialized(&self) -> bool {
+            self.get().is_some()
+        }
+
+        pub fn set(&self, value: T) -> Result<(), SetError<T>> {
+            if self.initialized() {
+                return Err(SetError::AlreadyInitializedError(value));
+            }
+            match self.semaphore.try_acquire() {
+                Ok(permit) => {
+                    permit.forget();
+                    self.initialize(value);
+                    Ok(())
+                }
+                Err(_) => Err(SetError::InitializingError(value)),
+            }
+        }
+
+        pub async fn get_or_init<F, Fut>(&self, f: F) -> &T
+        where
+            F: FnOnce() -> Fut,
+            Fut: Future<Output = T>,
+        {
+            match self
+                .get_or_try_init(|| async { Ok::<T, std::convert::Infallible>(f().await) })
+                .await
+            {
+                Ok(value) => value,
+                Err(never) => match never {},
+            }
+        }
+
+        pub async fn get_or_try_init<E, F, Fut>(&self, f: F) -> Result<&T, E>
+        where
+            F: FnOnce() -> Fut,
+            Fut: Future<Output = Result<T, E>>,
+        {
+            if let Some(value) = self.get() {
+                return Ok(value);
+            }
+            match self.semaphore.acquire().await {
+                Ok(permit) => {
+                    // on failure the permit goes back and the next waiter tries instead
+                    let value = f().await?;
+                    permit.forget();
+                    Ok(self.initialize(value))
+                }
+                Err(_) => Ok(self.get().unwrap()),
+            }
+        }
+
+        pub fn into_inner(self) -> Option<T> {
+            self.value.into_inner()
+        }
+
+        // only the holder of the semaphore's single permit gets here
+        fn initialize(&self, value: T) -> &T {
+            let value = self.value.get_or_init(|| value);
+            self.semaphore.close();
+            value
+        }
+    }
+
+    #[derive(PartialEq, Eq)]
+    pub enum SetError<T> {
+        AlreadyInitializedError(T),
+        InitializingError(T),
+    }
+
+    impl<T> std::fmt::Debug for SetError<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match *self {
+                SetError::AlreadyInitializedError(..) => "AlreadyInitializedError(..)".fmt(f),
+                SetError::InitializingError(..) => "InitializingError(..)".fmt(f),
+            }
+        }
+    }
+
+    impl<T> std::fmt::Display for SetError<T> {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match *self {
+                SetError::AlreadyInitializedError(..) => "cell already initialized".fmt(fmt),
+                SetError::InitializingError(..) => "cell is being initialized".fmt(fmt),
+            }
+        }
+    }
+
+    impl<T> std::error::Error for SetError<T> {}
+
+    pub mod oneshot {
+        use std::{
+            cell::{Cell, RefCell},
+            future::Future,
+            pin::Pin,
+            rc::Rc,
+            task::{Context, Poll, Waker},
+        };
+
+        use super::Notify;
+
+        pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+            let value = Rc::new(RefCell::new(Err(RecvError(()))));
+            let hungup = Rc::new(Cell::new(false));
+            let closed = Rc::new(Notify::new());
+            let waker = Rc::new(Cell::new(None));
+            (
+                Sender {
+                    value: value.clone(),
+                    hungup: hungup.clone(),
+                    closed: closed.clone(),
+                    waker: waker.clone(),
+                },
+                Receiver {
+                    value,
+                    hungup,
+                    closed,
+                    waker,
+                },
+            )
+        }
+
+        pub struct Sender<T> {
+            value: Rc<RefCell<Result<T, RecvError>>>,
+            hungup: Rc<Cell<bool>>,
+            closed: Rc<Notify>,
+            waker: Rc<Cell<Option<Waker>>>,
+        }
+
+        impl<T> Sender<T> {
+            pub fn send(self, value: T) -> Result<(), T> {
+                if self.hungup.get() {
+                    Err(value)
+                } else {
+                    *self.value.borrow_mut() = Ok(value);
+                    Ok(())
+                }
+            }
+
+            // only the receiver can raise the flag while a sender is alive
+            pub fn is_closed(&self) -> bool {
+                self.hungup.get()
+            }
+
+            pub async fn closed(&mut self) {
+                while !self.is_closed() {
+                    self.closed.notified().await;
+                }
+            }
+        }
+
+        impl<T> Drop for Sender<T> {
+            fn drop(&mut self) {
+                self.hungup.set(true);
+                if let Some(waker) = self.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+
+        #[derive(Debug, Eq, PartialEq, Clone)]
+        pub struct RecvError(());
+
+        #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+        pub enum TryRecvError {
+            Empty,
+            Closed,
+        }
+
+        impl std::fmt::Display for TryRecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TryRecvError::Empty => "channel empty".fmt(fmt),
+                    TryRecvError::Closed => "channel closed".fmt(fmt),
+                }
+            }
+        }
+
+        impl std::error::Error for TryRecvError {}
+
+        pub struct Receiver<T> {
+            value: Rc<RefCell<Result<T, RecvError>>>,
+            hungup: Rc<Cell<bool>>,
+            closed: Rc<Notify>,
+            waker: Rc<Cell<Option<Waker>>>,
+        }
+
+        impl<T> Receiver<T> {
+            pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+                match self.value.replace(Err(RecvError(()))) {
+                    Ok(value) => Ok(value),
+                    Err(_) if self.hungup.get() => Err(TryRecvError::Closed),
+                    Err(_) => Err(TryRecvError::Empty),
+                }
+            }
+
+            pub fn close(&mut self) {
+                self.hungup.set(true);
+                self.closed.notify_waiters();
+            }
+        }
+
+        impl<T> Future for Receiver<T> {
+            type Output = Result<T, RecvError>;
+
+            fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                // the sender hangs up when it's dropped, which also happens right after a send
+                if self.value.borrow().is_ok() || self.hungup.get() {
+                    Poll::Ready(self.value.replace(Err(RecvError(()))))
+                } else {
+                    self.waker.set(Some(cx.waker().clone()));
+                    Poll::Pending
+                }
+            }
+        }
+
+        impl<T> Drop for Receiver<T> {
+            fn drop(&mut self) {
+                self.close();
+            }
+        }
+    }
+
+    pub mod broadcast {
+        use std::{
+            cell::{Cell, RefCell},
+            collections::VecDeque,
+            rc::Rc,
+        };
+
+        use super::Notify;
+
+        pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+            assert!(capacity > 0, "broadcast channel capacity cannot be zero");
+            assert!(
+                capacity <= usize::MAX >> 1,
+                "broadcast channel capacity exceeded `usize::MAX / 2`"
+            );
+            // tokio rounds up too, so lagging happens at the same point on both
+            let capacity = capacity.next_power_of_two();
+            let shared = Rc::new(Shared {
+                buffer: RefCell::new(VecDeque::with_capacity(capacity)),
+                capacity,
+                head: Cell::new(0),
+                sender_count: Cell::new(1),
+                receiver_count: Cell::new(1),
+                notify: Notify::new(),
+            });
+            (
+                Sender {
+                    shared: shared.clone(),
+                },
+                Receiver { shared, next: 0 },
+            )
+        }
+
+        struct Shared<T> {
+            buffer: RefCell<VecDeque<T>>,
+            capacity: usize,
+            // position of the oldest value still in the buffer
+            head: Cell<u64>,
+            sender_count: Cell<usize>,
+            receiver_count: Cell<usize>,
+            notify: Notify,
+        }
+
+        impl<T> Shared<T> {
+            fn tail(&self) -> u64 {
+                self.head.get() + self.buffer.borrow().len() as u64
+            }
+        }
+
+        pub struct Sender<T> {
+            shared: Rc<Shared<T>>,
+        }
+
+        impl<T> std::fmt::Debug for Sender<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("Sender").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> Clone for Sender<T> {
+            fn clone(&self) -> Self {
+                self.shared
+                    .sender_count
+                    .set(self.shared.sender_count.get() + 1);
+                Self {
+                    shared: self.shared.clone(),
+                }
+            }
+        }
+
+        impl<T> Sender<T> {
+            pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+                let receiver_count = self.receiver_count();
+                if receiver_count == 0 {
+                    return Err(SendError(value));
+                }
+                {
+                    let mut buffer = self.shared.buffer.borrow_mut();
+                    if buffer.len() == self.shared.capacity {
+                        buffer.pop_front();
+                        self.shared.head.set(self.shared.head.get() + 1);
+                    }
+                    buffer.push_back(value);
+                }
+                self.shared.notify.notify_waiters();
+                Ok(receiver_count)
+            }
+
+            pub fn subscribe(&self) -> Receiver<T> {
+                self.shared
+                    .receiver_count
+                    .set(self.shared.receiver_count.get() + 1);
+                Receiver {
+                    shared: self.shared.clone(),
+                    next: self.shared.tail(),
+                }
+            }
+
+            pub fn receiver_count(&self) -> usize {
+                self.shared.receiver_count.get()
+            }
+        }
+
+        impl<T> Drop for Sender<T> {
+            fn drop(&mut self) {
+                self.shared
+                    .sender_count
+                    .set(self.shared.sender_count.get() - 1);
+                if self.shared.sender_count.get() == 0 {
+                    self.shared.notify.notify_waiters();
+                }
+            }
+        }
+
+        pub struct Receiver<T> {
+            shared: Rc<Shared<T>>,
+            next: u64,
+        }
+
+        impl<T> std::fmt::Debug for Receiver<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("Receiver").finish_non_exhaustive()
+            }
+        }
+
+        impl<T: Clone> Receiver<T> {
+            pub async fn recv(&mut self) -> Result<T, RecvError> {
+                loop {
+                    match self.try_recv() {
+                        Ok(value) => return Ok(value),
+                        Err(TryRecvError::Empty) => self.shared.notify.notified().await,
+                        Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                        Err(TryRecvError::Lagged(skipped)) => {
+                            return Err(RecvError::Lagged(skipped))
+                        }
+                    }
+                }
+            }
+
+            pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+                let head = self.shared.head.get();
+                if self.next < head {
+                    // skip ahead to the oldest value that's still around
+                    let skipped = head - self.next;
+                    self.next = head;
+                    return Err(TryRecvError::Lagged(skipped));
+                }
+                let value = self
+                    .shared
+                    .buffer
+                    .borrow()
+                    .get((self.next - head) as usize)
+                    .cloned();
+                if let Some(value) = value {
+                    self.next += 1;
+                    Ok(value)
+                } else if self.shared.sender_count.get() == 0 {
+                    Err(TryRecvError::Closed)
+                } else {
+                    Err(TryRecvError::Empty)
+                }
+            }
+
+            pub fn resubscribe(&self) -> Self {
+                self.shared
+                    .receiver_count
+                    .set(self.shared.receiver_count.get() + 1);
+                Self {
+                    shared: self.shared.clone(),
+                    next: self.shared.tail(),
+                }
+            }
+        }
+
+        impl<T> Drop for Receiver<T> {
+            fn drop(&mut self) {
+                self.shared
+                    .receiver_count
+                    .set(self.shared.receiver_count.get() - 1);
+            }
+        }
+
+        #[derive(PartialEq, Eq, Clone, Copy)]
+        pub struct SendError<T>(pub T);
+
+        impl<T> std::fmt::Debug for SendError<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("SendError").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> std::fmt::Display for SendError<T> {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(fmt, "channel closed")
+            }
+        }
+
+        impl<T> std::error::Error for SendError<T> {}
+
+        #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+        pub enum RecvError {
+            Closed,
+            Lagged(u64),
+        }
+
+        impl std::fmt::Display for RecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    RecvError::Closed => write!(fmt, "channel closed"),
+                    RecvError::Lagged(skipped) => write!(fmt, "channel lagged by {}", skipped),
+                }
+            }
+        }
+
+        impl std::error::Error for RecvError {}
+
+        #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+        pub enum TryRecvError {
+            Empty,
+            Closed,
+            Lagged(u64),
+        }
+
+        impl std::fmt::Display for TryRecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TryRecvError::Empty => write!(fmt, "channel empty"),
+                    TryRecvError::Closed => write!(fmt, "channel closed"),
+                    TryRecvError::Lagged(skipped) => write!(fmt, "channel lagged by {}", skipped),
+                }
+            }
+        }
+
+        impl std::error::Error for TryRecvError {}
+    }
+
+    pub mod watch {
+        use std::{
+            cell::{Cell, RefCell},
+            mem::replace,
+            ops::Deref,
+            rc::Rc,
+        };
+
+        use super::Notify;
+
+        pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+            let shared = Rc::new(Shared {
+                value: RefCell::new(init),
+                version: Cell::new(0),
+                sender_dropped: Cell::new(false),
+                receiver_count: Cell::new(1),
+                changed: Notify::new(),
+                closed: Notify::new(),
+            });
+            (
+                Sender {
+                    shared: shared.clone(),
+                },
+                Receiver { shared, version: 0 },
+            )
+        }
+
+        struct Shared<T> {
+            value: RefCell<T>,
+            version: Cell<usize>,
+            sender_dropped: Cell<bool>,
+            receiver_count: Cell<usize>,
+            changed: Notify,
+            closed: Notify,
+        }
+
+        pub struct Sender<T> {
+            shared: Rc<Shared<T>>,
+        }
+
+        impl<T> std::fmt::Debug for Sender<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("Sender").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> Sender<T> {
+            pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+                if self.is_closed() {
+                    Err(SendError(value))
+                } else {
+                    self.send_replace(value);
+                    Ok(())
+                }
+            }
+
+            pub fn send_modify<F>(&self, modify: F)
+            where
+                F: FnOnce(&mut T),
+            {
+                modify(&mut self.shared.value.borrow_mut());
+                self.notify_changed();
+            }
+
+            pub fn send_replace(&self, value: T) -> T {
+                let value = replace(&mut *self.shared.value.borrow_mut(), value);
+                self.notify_changed();
+                value
+            }
+
+            pub fn borrow(&self) -> Ref<'_, T> {
+                Ref(self.shared.value.borrow())
+            }
+
+            pub fn subscribe(&self) -> Receiver<T> {
+                self.shared
+                    .receiver_count
+                    .set(self.shared.receiver_count.get() + 1);
+                Receiver {
+                    shared: self.shared.clone(),
+                    version: self.shared.version.get(),
+                }
+            }
+
+            pub fn receiver_count(&self) -> usize {
+                self.shared.receiver_count.get()
+            }
+
+            pub fn is_closed(&self) -> bool {
+                self.receiver_count() == 0
+            }
+
+            pub async fn closed(&self) {
+                while !self.is_closed() {
+                    self.shared.closed.notified().await;
+                }
+            }
+
+            fn notify_changed(&self) {
+                self.shared.version.set(self.shared.version.get() + 1);
+                self.shared.changed.notify_waiters();
+            }
+        }
+
+        impl<T> Drop for Sender<T> {
+            fn drop(&mut self) {
+                self.shared.sender_dropped.set(true);
+                self.shared.changed.notify_waiters();
+            }
+        }
+
+        pub struct Receiver<T> {
+            shared: Rc<Shared<T>>,
+            version: usize,
+        }
+
+        impl<T> std::fmt::Debug for Receiver<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("Receiver").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> Clone for Receiver<T> {
+            fn clone(&self) -> Self {
+                self.shared
+                    .receiver_count
+                    .set(self.shared.receiver_count.get() + 1);
+                Self {
+                    shared: self.shared.clone(),
+                    version: self.version,
+                }
+            }
+        }
+
+        impl<T> Receiver<T> {
+            pub async fn changed(&mut self) -> Result<(), RecvError> {
+                loop {
+                    let version = self.shared.version.get();
+                    if version != self.version {
+                        self.version = version;
+                        return Ok(());
+                    } else if self.shared.sender_dropped.get() {
+                        return Err(RecvError(()));
+                    }
+                    self.shared.changed.notified().await;
+                }
+            }
+
+            pub fn has_changed(&self) -> Result<bool, RecvError> {
+                if self.shared.sender_dropped.get() {
+                    Err(RecvError(()))
+                } else {
+                    Ok(self.shared.version.get() != self.version)
+                }
+            }
+
+            pub fn borrow(&self) -> Ref<'_, T> {
+                Ref(self.shared.value.borrow())
+            }
+
+            pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+                self.version = self.shared.version.get();
+                Ref(self.shared.value.borrow())
+            }
         }
-    }
 
-    impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
-        fn drop(&mut self) {
-            *self.lock.readers.as_mut() -= 1;
-            if *self.lock.readers.as_ref().as_ref() == 0 && *self.lock.writers.as_ref().as_ref() > 0
-            {
-                self.lock.write_notify.notify_one();
+        impl<T> Drop for Receiver<T> {
+            fn drop(&mut self) {
+                self.shared
+                    .receiver_count
+                    .set(self.shared.receiver_count.get() - 1);
+                if self.shared.receiver_count.get() == 0 {
+                    self.shared.closed.notify_waiters();
+                }
             }
         }
-    }
-
-    pub mod oneshot {
-        use std::{
-            future::Future,
-            mem::replace,
-            pin::Pin,
-            sync::Arc,
-            task::{Context, Poll},
-        };
 
-        use js_sys::{Function, Promise};
-        use wasm_bindgen::JsValue;
-        use wasm_bindgen_futures::JsFuture;
+        // holding a `Ref` across a send panics instead of deadlocking like tokio
+        pub struct Ref<'a, T>(std::cell::Ref<'a, T>);
 
-        use super::InteriorMutability;
+        impl<'a, T> Deref for Ref<'a, T> {
+            type Target = T;
 
-        pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
-            let value = Arc::new(InteriorMutability::new(Err(RecvError(()))));
-            let hungup = Arc::new(InteriorMutability::new(false));
-            let mut resolve_fn = None;
-            let future = JsFuture::from(Promise::new(&mut |resolve, _reject| {
-                resolve_fn = Some(resolve);
-            }));
-            (
-                Sender {
-                    value: value.clone(),
-                    hungup: hungup.clone(),
-                    resolve: resolve_fn.unwrap(),
-                },
-                Receiver {
-                    value,
-                    hungup,
-                    future,
-                },
-            )
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
         }
 
-        pub struct Sender<T> {
-            value: Arc<InteriorMutability<Result<T, RecvError>>>,
-            hungup: Arc<InteriorMutability<bool>>,
-            resolve: Function,
-        }
+        #[derive(PartialEq, Eq, Clone, Copy)]
+        pub struct SendError<T>(pub T);
 
-        impl<T> Sender<T> {
-            pub fn send(self, value: T) -> Result<(), T> {
-                if *self.hungup.as_mut() {
-                    Err(value)
-                } else {
-                    *self.value.as_mut() = Ok(value);
-                    self.resolve.call0(&JsValue::null()).unwrap();
-                    Ok(())
-                }
+        impl<T> std::fmt::Debug for SendError<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("SendError").finish_non_exhaustive()
             }
         }
 
-        impl<T> Drop for Sender<T> {
-            fn drop(&mut self) {
-                *self.hungup.as_mut() = true;
-                self.resolve.call0(&JsValue::null()).unwrap();
+        impl<T> std::fmt::Display for SendError<T> {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(fmt, "channel closed")
             }
         }
 
+        impl<T> std::error::Error for SendError<T> {}
+
         #[derive(Debug, Eq, PartialEq, Clone)]
         pub struct RecvError(());
 
-        pub struct Receiver<T> {
-            value: Arc<InteriorMutability<Result<T, RecvError>>>,
-            hungup: Arc<InteriorMutability<bool>>,
-            future: JsFuture,
-        }
-
-        impl<T> Future for Receiver<T> {
-            type Output = Result<T, RecvError>;
-
-            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                Future::poll(Pin::new(&mut self.future), cx)
-                    .map(|_| replace(self.value.as_mut(), Err(RecvError(()))))
+        impl std::fmt::Display for RecvError {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(fmt, "channel closed")
             }
         }
 
-        impl<T> Drop for Receiver<T> {
-            fn drop(&mut self) {
-                *self.hungup.as_mut() = true;
-            }
-        }
+        impl std::error::Error for RecvError {}
     }
 
     pub mod mpsc {
-        use std::{collections::VecDeque, sync::Arc};
+        use std::{
+            cell::{Cell, RefCell},
+            collections::VecDeque,
+            future::Future,
+            pin::Pin,
+            rc::Rc,
+            task::{Context, Poll},
+        };
+
+        use futures_core::Stream;
 
-        use super::{InteriorMutability, Notify};
+        use super::{Notify, Semaphore, TryAcquireError};
 
         pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
+            assert!(buffer > 0, "mpsc bounded channel requires buffer > 0");
+            new_channel(Some(buffer))
+        }
+
+        pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+            let (sender, receiver) = new_channel(None);
+            (UnboundedSender(sender), UnboundedReceiver(receiver))
+        }
+
+        fn new_channel<T>(buffer: Option<usize>) -> (Sender<T>, Receiver<T>) {
             let mut data = VecDeque::new();
-            data.reserve_exact(buffer);
-            let data = Arc::new(InteriorMutability::new(data));
-            let hungup = Arc::new(InteriorMutability::new(false));
-            let notify = Arc::new(Notify::new());
+            data.reserve_exact(buffer.unwrap_or(0));
+            let data = Rc::new(RefCell::new(data));
+            let hungup = Rc::new(Cell::new(false));
+            let notify = Rc::new(Notify::new());
+            let closed = Rc::new(Notify::new());
+            let semaphore = buffer.map(|buffer| Rc::new(Semaphore::new(buffer)));
+            let overflow = Rc::new(Cell::new(0));
+            // permits handed out by `reserve` that haven't been used yet
+            let reserved = Rc::new(Cell::new(0));
             (
                 Sender {
-                    reference_count: Arc::new(InteriorMutability::new(1)),
+                    reference_count: Rc::new(Cell::new(1)),
                     data: data.clone(),
                     hungup: hungup.clone(),
                     notify: notify.clone(),
+                    closed: closed.clone(),
+                    semaphore: semaphore.clone(),
+                    overflow: overflow.clone(),
+                    reserved: reserved.clone(),
+                    max_capacity: buffer.unwrap_or(usize::MAX),
                 },
                 Receiver {
                     data,
                     hungup,
                     notify,
+                    closed,
+                    semaphore,
+                    overflow,
+                    reserved,
+                    notified: None,
                 },
             )
         }
 
         pub struct Sender<T> {
-            reference_count: Arc<InteriorMutability<usize>>,
-            data: Arc<InteriorMutability<VecDeque<T>>>,
-            hungup: Arc<InteriorMutability<bool>>,
-            notify: Arc<Notify>,
+            reference_count: Rc<Cell<usize>>,
+            data: Rc<RefCell<VecDeque<T>>>,
+            hungup: Rc<Cell<bool>>,
+            notify: Rc<Notify>,
+            closed: Rc<Notify>,
+            semaphore: Option<Rc<Semaphore>>,
+            overflow: Rc<Cell<usize>>,
+            reserved: Rc<Cell<usize>>,
+            max_capacity: usize,
         }
 
         impl<T> std::fmt::Debug for Sender<T> {
@@ -498,42 +3256,283 @@ mod wasm {
 
         impl<T> Clone for Sender<T> {
             fn clone(&self) -> Self {
-                *self.reference_count.as_mut() += 1;
+                self.reference_count.set(self.reference_count.get() + 1);
                 Self {
                     reference_count: self.reference_count.clone(),
                     data: self.data.clone(),
                     hungup: self.hungup.clone(),
                     notify: self.notify.clone(),
+                    closed: self.closed.clone(),
+                    semaphore: self.semaphore.clone(),
+                    overflow: self.overflow.clone(),
+                    reserved: self.reserved.clone(),
+                    max_capacity: self.max_capacity,
                 }
             }
         }
 
         impl<T> Sender<T> {
             pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
-                self.blocking_send(value)
+                match self.reserve().await {
+                    Ok(permit) => {
+                        permit.send(value);
+                        Ok(())
+                    }
+                    Err(_) => Err(SendError(value)),
+                }
             }
 
             pub fn blocking_send(&self, value: T) -> Result<(), SendError<T>> {
-                if *self.hungup.as_ref().as_ref() {
+                if let Some(semaphore) = &self.semaphore {
+                    match semaphore.try_acquire() {
+                        Ok(permit) => permit.forget(),
+                        Err(TryAcquireError::Closed) => return Err(SendError(value)),
+                        // the browser thread can't block, so the value goes in over capacity
+                        // and the receiver swallows the missing permit when it takes it out
+                        Err(TryAcquireError::NoPermits) => {
+                            self.overflow.set(self.overflow.get() + 1)
+                        }
+                    }
+                }
+                self.push(value)
+            }
+
+            pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+                if self.hungup.get() {
+                    return Err(TrySendError::Closed(value));
+                }
+                if let Some(semaphore) = &self.semaphore {
+                    match semaphore.try_acquire() {
+                        Ok(permit) => permit.forget(),
+                        Err(TryAcquireError::Closed) => return Err(TrySendError::Closed(value)),
+                        Err(TryAcquireError::NoPermits) => return Err(TrySendError::Full(value)),
+                    }
+                }
+                self.push(value).map_err(TrySendError::from)
+            }
+
+            pub async fn reserve(&self) -> Result<Permit<'_, T>, SendError<()>> {
+                if let Some(semaphore) = &self.semaphore {
+                    match semaphore.acquire().await {
+                        Ok(permit) => permit.forget(),
+                        Err(_) => return Err(SendError(())),
+                    }
+                }
+                self.reserved.set(self.reserved.get() + 1);
+                Ok(Permit { sender: Some(self) })
+            }
+
+            pub fn capacity(&self) -> usize {
+                self.semaphore
+                    .as_ref()
+                    .map_or(usize::MAX, |semaphore| semaphore.available_permits())
+            }
+
+            pub fn max_capacity(&self) -> usize {
+                self.max_capacity
+            }
+
+            pub async fn closed(&self) {
+                while !self.is_closed() {
+                    self.closed.notified().await;
+                }
+            }
+
+            pub fn is_closed(&self) -> bool {
+                self.hungup.get()
+            }
+
+            pub fn same_channel(&self, other: &Self) -> bool {
+                Rc::ptr_eq(&self.data, &other.data)
+            }
+
+            pub fn downgrade(&self) -> WeakSender<T> {
+                WeakSender {
+                    reference_count: self.reference_count.clone(),
+                    data: self.data.clone(),
+                    hungup: self.hungup.clone(),
+                    notify: self.notify.clone(),
+                    closed: self.closed.clone(),
+                    semaphore: self.semaphore.clone(),
+                    overflow: self.overflow.clone(),
+                    reserved: self.reserved.clone(),
+                    max_capacity: self.max_capacity,
+                }
+            }
+
+            fn push(&self, value: T) -> Result<(), SendError<T>> {
+                if self.hungup.get() {
                     Err(SendError(value))
                 } else {
-                    self.data.as_mut().push_back(value);
-                    self.notify.notify_waiters();
+                    self.deliver(value);
                     Ok(())
                 }
             }
+
+            fn deliver(&self, value: T) {
+                self.data.borrow_mut().push_back(value);
+                self.notify.notify_waiters();
+            }
         }
 
         impl<T> Drop for Sender<T> {
             fn drop(&mut self) {
-                *self.reference_count.as_mut() -= 1;
-                if *self.reference_count.as_ref().as_ref() == 0 {
-                    *self.hungup.as_mut() = true;
+                self.reference_count.set(self.reference_count.get() - 1);
+                if self.reference_count.get() == 0 {
+                    self.hungup.set(true);
                     self.notify.notify_waiters();
                 }
             }
         }
 
+        pub struct WeakSender<T> {
+            reference_count: Rc<Cell<usize>>,
+            data: Rc<RefCell<VecDeque<T>>>,
+            hungup: Rc<Cell<bool>>,
+            notify: Rc<Notify>,
+            closed: Rc<Notify>,
+            semaphore: Option<Rc<Semaphore>>,
+            overflow: Rc<Cell<usize>>,
+            reserved: Rc<Cell<usize>>,
+            max_capacity: usize,
+        }
+
+        impl<T> std::fmt::Debug for WeakSender<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("WeakSender").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> Clone for WeakSender<T> {
+            fn clone(&self) -> Self {
+                Self {
+                    reference_count: self.reference_count.clone(),
+                    data: self.data.clone(),
+                    hungup: self.hungup.clone(),
+                    notify: self.notify.clone(),
+                    closed: self.closed.clone(),
+                    semaphore: self.semaphore.clone(),
+                    overflow: self.overflow.clone(),
+                    reserved: self.reserved.clone(),
+                    max_capacity: self.max_capacity,
+                }
+            }
+        }
+
+        impl<T> WeakSender<T> {
+            pub fn upgrade(&self) -> Option<Sender<T>> {
+                if self.reference_count.get() == 0 {
+                    return None;
+                }
+                self.reference_count.set(self.reference_count.get() + 1);
+                Some(Sender {
+                    reference_count: self.reference_count.clone(),
+                    data: self.data.clone(),
+                    hungup: self.hungup.clone(),
+                    notify: self.notify.clone(),
+                    closed: self.closed.clone(),
+                    semaphore: self.semaphore.clone(),
+                    overflow: self.overflow.clone(),
+                    reserved: self.reserved.clone(),
+                    max_capacity: self.max_capacity,
+                })
+            }
+        }
+
+        pub struct Permit<'a, T> {
+            sender: Option<&'a Sender<T>>,
+        }
+
+        impl<'a, T> std::fmt::Debug for Permit<'a, T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("Permit").finish_non_exhaustive()
+            }
+        }
+
+        impl<'a, T> Permit<'a, T> {
+            // like tokio, a permit reserved before the receiver closed the
+            // channel can still deliver its value
+            pub fn send(mut self, value: T) {
+                if let Some(sender) = self.sender.take() {
+                    sender.reserved.set(sender.reserved.get() - 1);
+                    sender.deliver(value);
+                }
+            }
+        }
+
+        impl<'a, T> Drop for Permit<'a, T> {
+            fn drop(&mut self) {
+                let Some(sender) = self.sender else {
+                    return;
+                };
+                if let Some(semaphore) = &sender.semaphore {
+                    semaphore.add_permits(1);
+                }
+                sender.reserved.set(sender.reserved.get() - 1);
+                // a closed channel's receiver may be waiting on the last one
+                if sender.reserved.get() == 0 && sender.hungup.get() {
+                    sender.notify.notify_waiters();
+                }
+            }
+        }
+
+        pub struct UnboundedSender<T>(Sender<T>);
+
+        impl<T> std::fmt::Debug for UnboundedSender<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("UnboundedSender").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> Clone for UnboundedSender<T> {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T> UnboundedSender<T> {
+            pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+                self.0.push(value)
+            }
+
+            pub async fn closed(&self) {
+                self.0.closed().await
+            }
+
+            pub fn is_closed(&self) -> bool {
+                self.0.is_closed()
+            }
+
+            pub fn same_channel(&self, other: &Self) -> bool {
+                self.0.same_channel(&other.0)
+            }
+
+            pub fn downgrade(&self) -> WeakUnboundedSender<T> {
+                WeakUnboundedSender(self.0.downgrade())
+            }
+        }
+
+        pub struct WeakUnboundedSender<T>(WeakSender<T>);
+
+        impl<T> std::fmt::Debug for WeakUnboundedSender<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("WeakUnboundedSender")
+                    .finish_non_exhaustive()
+            }
+        }
+
+        impl<T> Clone for WeakUnboundedSender<T> {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T> WeakUnboundedSender<T> {
+            pub fn upgrade(&self) -> Option<UnboundedSender<T>> {
+                self.0.upgrade().map(UnboundedSender)
+            }
+        }
+
         #[derive(PartialEq, Eq, Clone, Copy)]
         pub struct SendError<T>(pub T);
 
@@ -551,6 +3550,38 @@ mod wasm {
 
         impl<T> std::error::Error for SendError<T> {}
 
+        #[derive(PartialEq, Eq, Clone, Copy)]
+        pub enum TrySendError<T> {
+            Full(T),
+            Closed(T),
+        }
+
+        impl<T> std::fmt::Debug for TrySendError<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TrySendError::Full(..) => "Full(..)".fmt(f),
+                    TrySendError::Closed(..) => "Closed(..)".fmt(f),
+                }
+            }
+        }
+
+        impl<T> std::fmt::Display for TrySendError<T> {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    TrySendError::Full(..) => "no available capacity".fmt(fmt),
+                    TrySendError::Closed(..) => "channel closed".fmt(fmt),
+                }
+            }
+        }
+
+        impl<T> std::error::Error for TrySendError<T> {}
+
+        impl<T> From<SendError<T>> for TrySendError<T> {
+            fn from(err: SendError<T>) -> Self {
+                TrySendError::Closed(err.0)
+            }
+        }
+
         #[derive(PartialEq, Eq, Clone, Copy, Debug)]
         pub enum TryRecvError {
             Empty,
@@ -569,9 +3600,14 @@ mod wasm {
         impl std::error::Error for TryRecvError {}
 
         pub struct Receiver<T> {
-            data: Arc<InteriorMutability<VecDeque<T>>>,
-            hungup: Arc<InteriorMutability<bool>>,
-            notify: Arc<Notify>,
+            data: Rc<RefCell<VecDeque<T>>>,
+            hungup: Rc<Cell<bool>>,
+            notify: Rc<Notify>,
+            closed: Rc<Notify>,
+            semaphore: Option<Rc<Semaphore>>,
+            overflow: Rc<Cell<usize>>,
+            reserved: Rc<Cell<usize>>,
+            notified: Option<Pin<Box<dyn Future<Output = ()>>>>,
         }
 
         impl<T> std::fmt::Debug for Receiver<T> {
@@ -585,9 +3621,9 @@ mod wasm {
         impl<T> Receiver<T> {
             pub async fn recv(&mut self) -> Option<T> {
                 loop {
-                    if let Some(value) = self.data.as_mut().pop_front() {
+                    if let Some(value) = self.pop() {
                         return Some(value);
-                    } else if *self.hungup.as_ref().as_ref() {
+                    } else if self.is_finished() {
                         return None;
                     }
                     self.notify.notified().await;
@@ -595,22 +3631,138 @@ mod wasm {
             }
 
             pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
-                if let Some(value) = self.data.as_mut().pop_front() {
-                    return Ok(value);
-                } else if *self.hungup.as_ref().as_ref() {
-                    return Err(TryRecvError::Disconnected);
+                if let Some(value) = self.pop() {
+                    Ok(value)
+                } else if self.is_finished() {
+                    Err(TryRecvError::Disconnected)
                 } else {
-                    return Err(TryRecvError::Empty);
+                    Err(TryRecvError::Empty)
+                }
+            }
+
+            pub async fn recv_many(&mut self, buffer: &mut Vec<T>, limit: usize) -> usize {
+                if limit == 0 {
+                    return 0;
+                }
+                loop {
+                    let start = buffer.len();
+                    while buffer.len() - start < limit {
+                        match self.pop() {
+                            Some(value) => buffer.push(value),
+                            None => break,
+                        }
+                    }
+                    if buffer.len() > start || self.is_finished() {
+                        return buffer.len() - start;
+                    }
+                    self.notify.notified().await;
+                }
+            }
+
+            pub fn close(&mut self) {
+                self.hungup.set(true);
+                if let Some(semaphore) = &self.semaphore {
+                    semaphore.close();
+                }
+                self.closed.notify_waiters();
+            }
+
+            pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+                loop {
+                    if let Some(value) = self.pop() {
+                        self.notified = None;
+                        return Poll::Ready(Some(value));
+                    } else if self.is_finished() {
+                        self.notified = None;
+                        return Poll::Ready(None);
+                    }
+                    let notify = self.notify.clone();
+                    let notified = self
+                        .notified
+                        .get_or_insert_with(|| Box::pin(async move { notify.notified().await }));
+                    std::task::ready!(notified.as_mut().poll(cx));
+                    self.notified = None;
+                }
+            }
+
+            // closed, and no reserved permit can still send a value
+            fn is_finished(&self) -> bool {
+                self.hungup.get() && self.reserved.get() == 0
+            }
+
+            fn pop(&mut self) -> Option<T> {
+                let value = self.data.borrow_mut().pop_front()?;
+                if let Some(semaphore) = &self.semaphore {
+                    let overflow = self.overflow.get();
+                    if overflow > 0 {
+                        self.overflow.set(overflow - 1);
+                    } else {
+                        semaphore.add_permits(1);
+                    }
                 }
+                Some(value)
+            }
+        }
+
+        impl<T> Stream for Receiver<T> {
+            type Item = T;
+
+            fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+                self.poll_recv(cx)
             }
         }
 
         impl<T> Drop for Receiver<T> {
             fn drop(&mut self) {
-                *self.hungup.as_mut() = true;
+                self.close();
+            }
+        }
+
+        pub struct UnboundedReceiver<T>(Receiver<T>);
+
+        impl<T> std::fmt::Debug for UnboundedReceiver<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("UnboundedReceiver").finish_non_exhaustive()
+            }
+        }
+
+        impl<T> UnboundedReceiver<T> {
+            pub async fn recv(&mut self) -> Option<T> {
+                self.0.recv().await
+            }
+
+            pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+                self.0.try_recv()
+            }
+
+            pub async fn recv_many(&mut self, buffer: &mut Vec<T>, limit: usize) -> usize {
+                self.0.recv_many(buffer, limit).await
+            }
+
+            pub fn close(&mut self) {
+                self.0.close()
+            }
+
+            pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+                self.0.poll_recv(cx)
+            }
+        }
+
+        impl<T> Stream for UnboundedReceiver<T> {
+            type Item = T;
+
+            fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+                self.poll_recv(cx)
             }
         }
     }
+
+    coordination!();
 }
 #[cfg(target_arch = "wasm32")]
 pub use wasm::*;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "wasm-sim"))]
+pub mod sim {
+    pub use super::wasm::*;
+}
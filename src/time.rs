@@ -1,43 +1,1308 @@
+// wall-clock time, which each backend reads through its own `system_now`
+macro_rules! system_time {
+    () => {
+        pub use std::time::SystemTimeError;
+
+        pub const UNIX_EPOCH: SystemTime = SystemTime(std::time::UNIX_EPOCH);
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct SystemTime(std::time::SystemTime);
+
+        impl SystemTime {
+            pub const UNIX_EPOCH: SystemTime = UNIX_EPOCH;
+
+            pub fn now() -> Self {
+                Self(system_now())
+            }
+
+            pub fn duration_since(&self, earlier: SystemTime) -> Result<Duration, SystemTimeError> {
+                self.0.duration_since(earlier.0)
+            }
+
+            pub fn elapsed(&self) -> Result<Duration, SystemTimeError> {
+                SystemTime::now().duration_since(*self)
+            }
+
+            pub fn checked_add(&self, duration: Duration) -> Option<SystemTime> {
+                self.0.checked_add(duration).map(Self)
+            }
+
+            pub fn checked_sub(&self, duration: Duration) -> Option<SystemTime> {
+                self.0.checked_sub(duration).map(Self)
+            }
+        }
+
+        impl Add<Duration> for SystemTime {
+            type Output = SystemTime;
+
+            fn add(self, rhs: Duration) -> SystemTime {
+                Self(self.0 + rhs)
+            }
+        }
+
+        impl AddAssign<Duration> for SystemTime {
+            fn add_assign(&mut self, rhs: Duration) {
+                self.0 += rhs;
+            }
+        }
+
+        impl Sub<Duration> for SystemTime {
+            type Output = SystemTime;
+
+            fn sub(self, rhs: Duration) -> SystemTime {
+                Self(self.0 - rhs)
+            }
+        }
+
+        impl SubAssign<Duration> for SystemTime {
+            fn sub_assign(&mut self, rhs: Duration) {
+                self.0 -= rhs;
+            }
+        }
+
+        impl From<std::time::SystemTime> for SystemTime {
+            fn from(time: std::time::SystemTime) -> Self {
+                Self(time)
+            }
+        }
+
+        impl From<SystemTime> for std::time::SystemTime {
+            fn from(time: SystemTime) -> Self {
+                time.0
+            }
+        }
+    };
+}
+
+// the time a frame started at, on the same clock as each backend's `Instant`
+macro_rules! frame_time {
+    () => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct FrameTime(Instant);
+
+        impl FrameTime {
+            pub fn instant(&self) -> Instant {
+                self.0
+            }
+
+            pub fn duration_since(&self, earlier: FrameTime) -> Duration {
+                self.0.saturating_duration_since(earlier.0)
+            }
+        }
+    };
+}
+
+pub mod delay_queue;
+pub mod schedule;
+
 #[cfg(not(target_arch = "wasm32"))]
 mod native {
-    use std::time::Duration;
+    #[cfg(feature = "test-util")]
+    use std::cell::Cell;
+    use std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+        ops::{Add, AddAssign, Sub, SubAssign},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            OnceLock,
+        },
+        time::Duration,
+    };
+
+    use super::MissedTickBehavior;
+    #[cfg(feature = "idle")]
+    use crate::Context;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant(tokio::time::Instant);
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self(tokio::time::Instant::now())
+        }
+
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            self.0.duration_since(earlier.0)
+        }
+
+        pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+            self.0.checked_duration_since(earlier.0)
+        }
+
+        pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            self.0.saturating_duration_since(earlier.0)
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            self.0.elapsed()
+        }
+
+        pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_add(duration).map(Self)
+        }
+
+        pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_sub(duration).map(Self)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, rhs: Duration) -> Instant {
+            Self(self.0 + rhs)
+        }
+    }
+
+    impl AddAssign<Duration> for Instant {
+        fn add_assign(&mut self, rhs: Duration) {
+            self.0 += rhs;
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+
+        fn sub(self, rhs: Duration) -> Instant {
+            Self(self.0 - rhs)
+        }
+    }
+
+    impl SubAssign<Duration> for Instant {
+        fn sub_assign(&mut self, rhs: Duration) {
+            self.0 -= rhs;
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+
+        fn sub(self, rhs: Instant) -> Duration {
+            self.0 - rhs.0
+        }
+    }
 
     pub async fn sleep(duration: Duration) {
         tokio::time::sleep(duration).await;
     }
+
+    pub async fn sleep_until(deadline: Instant) {
+        tokio::time::sleep_until(deadline.0).await;
+    }
+
+    #[cfg(not(feature = "test-util"))]
+    fn system_now() -> std::time::SystemTime {
+        std::time::SystemTime::now()
+    }
+
+    // the wall clock as moved by `pause`, `advance` and `resume`. it's kept per
+    // thread, like the current-thread runtime whose clock they control
+    #[cfg(feature = "test-util")]
+    #[derive(Clone, Copy)]
+    enum WallClock {
+        // follows tokio's paused clock from the wall time it was paused at
+        Paused {
+            wall: std::time::SystemTime,
+            at: tokio::time::Instant,
+        },
+        // reads the real wall clock, shifted by however far time was advanced
+        Shifted {
+            wall: std::time::SystemTime,
+            real: std::time::SystemTime,
+        },
+    }
+
+    #[cfg(feature = "test-util")]
+    thread_local! {
+        static WALL_CLOCK: Cell<Option<WallClock>> = const { Cell::new(None) };
+    }
+
+    #[cfg(feature = "test-util")]
+    fn system_now() -> std::time::SystemTime {
+        let real = std::time::SystemTime::now();
+        match WALL_CLOCK.get() {
+            None => real,
+            Some(WallClock::Paused { wall, at }) => {
+                wall + tokio::time::Instant::now().saturating_duration_since(at)
+            }
+            Some(WallClock::Shifted { wall, real: then }) => match real.duration_since(then) {
+                Ok(since) => wall + since,
+                Err(error) => wall - error.duration(),
+            },
+        }
+    }
+
+    pub(super) fn random() -> f64 {
+        // std seeds every `RandomState` differently, which is plenty for jitter
+        let bits = RandomState::new().build_hasher().finish();
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // these control the clock of the runtime they are called from, which has to
+    // be a current-thread one (like `#[tokio::test]`'s). tasks spawned with
+    // `tokasm::spawn` only follow it if they were spawned within
+    // `tokasm::enter_test_runtime`. everything else, including `tokasm::main`
+    // and the tasks it spawns, runs on tokasm's own runtime in real time
+    #[cfg(feature = "test-util")]
+    pub fn pause() {
+        let flavor = tokio::runtime::Handle::try_current().map(|handle| handle.runtime_flavor());
+        assert!(
+            matches!(flavor, Ok(tokio::runtime::RuntimeFlavor::CurrentThread)),
+            "`tokasm::time::pause` must be called from a current-thread runtime"
+        );
+        let wall = system_now();
+        tokio::time::pause();
+        WALL_CLOCK.set(Some(WallClock::Paused {
+            wall,
+            at: tokio::time::Instant::now(),
+        }));
+    }
+
+    #[cfg(feature = "test-util")]
+    pub fn resume() {
+        let wall = system_now();
+        tokio::time::resume();
+        WALL_CLOCK.set(Some(WallClock::Shifted {
+            wall,
+            real: std::time::SystemTime::now(),
+        }));
+    }
+
+    #[cfg(feature = "test-util")]
+    pub async fn advance(duration: Duration) {
+        // the runtime may have started paused, without going through `pause`
+        if !matches!(WALL_CLOCK.get(), Some(WallClock::Paused { .. })) {
+            WALL_CLOCK.set(Some(WallClock::Paused {
+                wall: system_now(),
+                at: tokio::time::Instant::now(),
+            }));
+        }
+        tokio::time::advance(duration).await;
+        // that only fires the timers, so yield once more to let the tasks they
+        // woke run before the caller continues, like the WASM clock does
+        tokio::task::yield_now().await;
+    }
+
+    // moves the wall clock to `time` without touching `Instant`, like a manual
+    // clock change or an NTP correction would
+    #[cfg(feature = "test-util")]
+    pub fn set_system_time(time: SystemTime) {
+        WALL_CLOCK.set(Some(match WALL_CLOCK.get() {
+            Some(WallClock::Shifted { .. }) => WallClock::Shifted {
+                wall: time.0,
+                real: std::time::SystemTime::now(),
+            },
+            // tokio's clock keeps real time unless it's paused, so this follows
+            // it either way, including on a runtime that started paused
+            None | Some(WallClock::Paused { .. }) => WallClock::Paused {
+                wall: time.0,
+                at: tokio::time::Instant::now(),
+            },
+        }));
+    }
+
+    pub fn interval(period: Duration) -> Interval {
+        Interval(tokio::time::interval(period))
+    }
+
+    pub fn interval_at(start: Instant, period: Duration) -> Interval {
+        Interval(tokio::time::interval_at(start.0, period))
+    }
+
+    #[derive(Debug)]
+    pub struct Interval(tokio::time::Interval);
+
+    impl Interval {
+        pub async fn tick(&mut self) -> Instant {
+            Instant(self.0.tick().await)
+        }
+
+        pub fn reset(&mut self) {
+            self.0.reset();
+        }
+
+        pub fn period(&self) -> Duration {
+            self.0.period()
+        }
+
+        pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+            match self.0.missed_tick_behavior() {
+                tokio::time::MissedTickBehavior::Burst => MissedTickBehavior::Burst,
+                tokio::time::MissedTickBehavior::Delay => MissedTickBehavior::Delay,
+                tokio::time::MissedTickBehavior::Skip => MissedTickBehavior::Skip,
+            }
+        }
+
+        pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+            self.0.set_missed_tick_behavior(match behavior {
+                MissedTickBehavior::Burst => tokio::time::MissedTickBehavior::Burst,
+                MissedTickBehavior::Delay => tokio::time::MissedTickBehavior::Delay,
+                MissedTickBehavior::Skip => tokio::time::MissedTickBehavior::Skip,
+            });
+        }
+    }
+
+    static FRAME_PERIOD_NANOS: AtomicU64 = AtomicU64::new(1_000_000_000 / 60);
+    static FRAME_ORIGIN: OnceLock<Instant> = OnceLock::new();
+
+    pub fn set_frame_rate(frames_per_second: u32) {
+        assert!(
+            frames_per_second > 0,
+            "`frames_per_second` must be non-zero."
+        );
+        // past a billion frames per second the period would round down to zero
+        let period = (1_000_000_000 / frames_per_second as u64).max(1);
+        FRAME_PERIOD_NANOS.store(period, Ordering::Relaxed);
+    }
+
+    pub async fn animation_frame() -> FrameTime {
+        // emulate vsync: every caller waiting on the same frame wakes at the
+        // same boundary and receives the same timestamp
+        let period = FRAME_PERIOD_NANOS.load(Ordering::Relaxed) as u128;
+        let origin = *FRAME_ORIGIN.get_or_init(Instant::now);
+        let frame = (Instant::now() - origin).as_nanos() / period + 1;
+        let time = origin + Duration::from_nanos((frame * period) as u64);
+        sleep_until(time).await;
+        FrameTime(time)
+    }
+
+    // only tasks spawned with the `idle` feature enabled are tracked, so that
+    // nothing pays for it unless it's used
+    #[cfg(feature = "idle")]
+    pub async fn idle() -> IdleDeadline {
+        let context = Context::singleton();
+        // check again after every wakeup, since a task can go idle for a moment
+        // before its deferred wakeup marks it active again. a tokasm task calling
+        // this stops counting as active once it waits here
+        loop {
+            // registered before checking, so the last task going idle in
+            // between isn't missed
+            let mut idle = std::pin::pin!(context.idle.notified());
+            idle.as_mut().enable();
+            if context.active_count.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            idle.await;
+        }
+        IdleDeadline {
+            // browsers never hand out more than 50ms of idle time at once
+            deadline: Instant::now() + Duration::from_millis(50),
+        }
+    }
+
+    // without the `idle` feature nothing tracks whether tasks are scheduled, so
+    // this can't know when the runtime is idle. it exists anyway so that code
+    // shared with the browser still builds
+    #[cfg(not(feature = "idle"))]
+    pub async fn idle() -> IdleDeadline {
+        panic!("`tokasm::time::idle` needs the `idle` feature outside the browser")
+    }
+
+    #[derive(Debug)]
+    pub struct IdleDeadline {
+        deadline: Instant,
+    }
+
+    impl IdleDeadline {
+        pub fn time_remaining(&self) -> Duration {
+            self.deadline.saturating_duration_since(Instant::now())
+        }
+    }
+
+    frame_time!();
+    system_time!();
 }
-use std::time::Duration;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_core::Stream;
 
+pub use delay_queue::DelayQueue;
 #[cfg(not(target_arch = "wasm32"))]
 pub use native::*;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(not(target_arch = "wasm32"))]
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+#[cfg(any(target_arch = "wasm32", feature = "wasm-sim"))]
 mod wasm {
-    use std::time::Duration;
+    use std::{
+        cell::RefCell,
+        collections::BTreeMap,
+        future::Future,
+        ops::{Add, AddAssign, Sub, SubAssign},
+        pin::Pin,
+        rc::Rc,
+        task::{Context, Poll, Waker},
+        time::Duration,
+    };
 
+    #[cfg(target_arch = "wasm32")]
     use js_sys::Promise;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen::JsCast;
+    #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_futures::JsFuture;
+    #[cfg(target_arch = "wasm32")]
     use web_sys::window;
 
+    #[cfg(not(target_arch = "wasm32"))]
+    use crate::sim::host;
+
+    use super::MissedTickBehavior;
+
+    // time since the page's time origin, as reported by `performance.now()`,
+    // shifted by however much the virtual clock has been advanced
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant(Duration);
+
+    impl Instant {
+        pub fn now() -> Self {
+            CLOCK.with(|clock| clock.borrow().now())
+        }
+
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+
+        pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+            self.0.checked_sub(earlier.0)
+        }
+
+        pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Instant::now().saturating_duration_since(*self)
+        }
+
+        pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_add(duration).map(Self)
+        }
+
+        pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_sub(duration).map(Self)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, rhs: Duration) -> Instant {
+            self.checked_add(rhs)
+                .expect("overflow when adding duration to instant")
+        }
+    }
+
+    impl AddAssign<Duration> for Instant {
+        fn add_assign(&mut self, rhs: Duration) {
+            *self = *self + rhs;
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+
+        fn sub(self, rhs: Duration) -> Instant {
+            self.checked_sub(rhs)
+                .expect("overflow when subtracting duration from instant")
+        }
+    }
+
+    impl SubAssign<Duration> for Instant {
+        fn sub_assign(&mut self, rhs: Duration) {
+            *self = *self - rhs;
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+
+        fn sub(self, rhs: Instant) -> Duration {
+            self.duration_since(rhs)
+        }
+    }
+
     pub async fn sleep(duration: Duration) {
-        let window = window().unwrap();
-        JsFuture::from(Promise::new(&mut |resolve, _reject| {
-            window
+        sleep_until(Instant::now() + duration).await;
+    }
+
+    pub async fn sleep_until(deadline: Instant) {
+        // timers can fire slightly early due to millisecond rounding, so keep
+        // sleeping until the deadline has actually passed
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            if CLOCK.with(|clock| clock.borrow().frozen.is_some()) {
+                VirtualSleep { deadline, id: None }.await;
+            } else {
+                timeout(deadline - now).await;
+            }
+        }
+    }
+
+    fn timeout(duration: Duration) -> Timeout {
+        let state = Rc::new(RefCell::new(TimeoutState {
+            fired: false,
+            waker: None,
+        }));
+        let callback = {
+            let state = state.clone();
+            move || {
+                let waker = {
+                    let mut state = state.borrow_mut();
+                    state.fired = true;
+                    state.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+        };
+        // `setTimeout` can't wait any longer than this, and `sleep_until` sets
+        // another one for whatever is left
+        let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+        Timeout {
+            state,
+            _timer: host::set_timeout(callback, millis),
+        }
+    }
+
+    // clears its `setTimeout` when dropped, so that replaced or abandoned
+    // sleeps don't leave callbacks behind
+    struct Timeout {
+        state: Rc<RefCell<TimeoutState>>,
+        _timer: host::Timer,
+    }
+
+    struct TimeoutState {
+        fired: bool,
+        waker: Option<Waker>,
+    }
+
+    impl Future for Timeout {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let mut state = self.state.borrow_mut();
+            if state.fired {
+                return Poll::Ready(());
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    // the browser APIs the clock is built on. `tokasm::sim` stands in for them
+    // when the `wasm-sim` feature builds this module natively
+    #[cfg(target_arch = "wasm32")]
+    mod host {
+        use wasm_bindgen::{closure::Closure, JsCast};
+        use web_sys::window;
+
+        pub fn now() -> f64 {
+            window().unwrap().performance().unwrap().now()
+        }
+
+        pub fn date_now() -> f64 {
+            js_sys::Date::now()
+        }
+
+        pub fn set_timeout(callback: impl FnOnce() + 'static, millis: i32) -> Timer {
+            let callback = Closure::once(callback);
+            let handle = window()
+                .unwrap()
                 .set_timeout_with_callback_and_timeout_and_arguments_0(
-                    &resolve,
-                    duration.as_millis() as i32,
+                    callback.as_ref().unchecked_ref(),
+                    millis,
                 )
                 .unwrap();
+            Timer {
+                handle,
+                _callback: callback,
+            }
+        }
+
+        // clears the timeout when dropped
+        pub struct Timer {
+            handle: i32,
+            _callback: Closure<dyn FnMut()>,
+        }
+
+        impl Drop for Timer {
+            fn drop(&mut self) {
+                window().unwrap().clear_timeout_with_handle(self.handle);
+            }
+        }
+
+        pub fn request_animation_frame(callback: impl FnOnce(f64) + 'static) -> FrameRequest {
+            let callback = Closure::once(callback);
+            let handle = window()
+                .unwrap()
+                .request_animation_frame(callback.as_ref().unchecked_ref())
+                .unwrap();
+            FrameRequest {
+                handle,
+                _callback: callback,
+            }
+        }
+
+        // cancels the request when dropped
+        pub struct FrameRequest {
+            handle: i32,
+            _callback: Closure<dyn FnMut(f64)>,
+        }
+
+        impl Drop for FrameRequest {
+            fn drop(&mut self) {
+                window()
+                    .unwrap()
+                    .cancel_animation_frame(self.handle)
+                    .unwrap();
+            }
+        }
+
+        // lets already woken tasks run, like awaiting a resolved promise
+        #[cfg(feature = "test-util")]
+        pub async fn yield_now() {
+            let resolved = js_sys::Promise::resolve(&wasm_bindgen::JsValue::UNDEFINED);
+            wasm_bindgen_futures::JsFuture::from(resolved)
+                .await
+                .unwrap();
+        }
+    }
+
+    thread_local! {
+        static CLOCK: RefCell<Clock> = const { RefCell::new(Clock {
+            frozen: None,
+            offset: 0.,
+            paused_wall: None,
+            wall_offset: 0.,
+            timers: BTreeMap::new(),
+            next_timer_id: 0,
+            auto_advance_scheduled: false,
+            auto_advance: None,
+        }) };
+    }
+
+    struct Clock {
+        frozen: Option<Instant>,
+        offset: f64,
+        // the wall time and instant the clock was frozen at
+        paused_wall: Option<(std::time::SystemTime, Instant)>,
+        // how far the wall clock has been shifted since, in milliseconds
+        wall_offset: f64,
+        timers: BTreeMap<(Instant, u64), Waker>,
+        next_timer_id: u64,
+        auto_advance_scheduled: bool,
+        // kept until the next one is scheduled, since the callback can't clear
+        // its own timer while it runs
+        auto_advance: Option<host::Timer>,
+    }
+
+    impl Clock {
+        fn now(&self) -> Instant {
+            self.instant_at(host::now())
+        }
+
+        // the instant for a time read from the page's clock
+        fn instant_at(&self, millis: f64) -> Instant {
+            self.frozen.unwrap_or_else(|| {
+                let millis = millis + self.offset;
+                Instant(Duration::from_secs_f64(millis.max(0.) / 1000.))
+            })
+        }
+
+        fn system_now(&self) -> std::time::SystemTime {
+            if let Some((wall, at)) = self.paused_wall {
+                return wall + self.now().saturating_duration_since(at);
+            }
+            let millis = host::date_now() + self.wall_offset;
+            std::time::UNIX_EPOCH + Duration::from_secs_f64(millis.max(0.) / 1000.)
+        }
+
+        fn take_due_timers(&mut self) -> Vec<Waker> {
+            let now = self.now();
+            let mut wakers = vec![];
+            while let Some(entry) = self.timers.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                wakers.push(entry.remove());
+            }
+            wakers
+        }
+
+        // like tokio, jump straight to the next timer once every task is
+        // waiting on something, which in the browser is the next macrotask
+        fn schedule_auto_advance(&mut self) {
+            if self.auto_advance_scheduled {
+                return;
+            }
+            self.auto_advance_scheduled = true;
+            let callback = || {
+                let wakers = CLOCK.with(|clock| {
+                    let mut clock = clock.borrow_mut();
+                    clock.auto_advance_scheduled = false;
+                    let Some(frozen) = clock.frozen else {
+                        return vec![];
+                    };
+                    if let Some(&(deadline, _)) = clock.timers.keys().next() {
+                        clock.frozen = Some(frozen.max(deadline));
+                    }
+                    clock.take_due_timers()
+                });
+                for waker in wakers {
+                    waker.wake();
+                }
+            };
+            self.auto_advance = Some(host::set_timeout(callback, 0));
+        }
+    }
+
+    struct VirtualSleep {
+        deadline: Instant,
+        id: Option<u64>,
+    }
+
+    impl Future for VirtualSleep {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            CLOCK.with(|clock| {
+                let mut clock = clock.borrow_mut();
+                if let Some(id) = self.id.take() {
+                    clock.timers.remove(&(self.deadline, id));
+                }
+                if clock.frozen.is_none() || clock.now() >= self.deadline {
+                    return Poll::Ready(());
+                }
+                let id = clock.next_timer_id;
+                clock.next_timer_id += 1;
+                clock.timers.insert((self.deadline, id), cx.waker().clone());
+                clock.schedule_auto_advance();
+                self.id = Some(id);
+                Poll::Pending
+            })
+        }
+    }
+
+    impl Drop for VirtualSleep {
+        fn drop(&mut self) {
+            if let Some(id) = self.id {
+                CLOCK.with(|clock| clock.borrow_mut().timers.remove(&(self.deadline, id)));
+            }
+        }
+    }
+
+    // `std::time::SystemTime::now` panics in the browser, so read `Date.now()`
+    // instead
+    fn system_now() -> std::time::SystemTime {
+        CLOCK.with(|clock| clock.borrow().system_now())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(super) fn random() -> f64 {
+        js_sys::Math::random()
+    }
+
+    #[cfg(feature = "test-util")]
+    pub fn pause() {
+        CLOCK.with(|clock| {
+            let mut clock = clock.borrow_mut();
+            assert!(clock.frozen.is_none(), "time is already frozen");
+            let now = clock.now();
+            clock.paused_wall = Some((clock.system_now(), now));
+            clock.frozen = Some(now);
+        });
+    }
+
+    #[cfg(feature = "test-util")]
+    pub fn resume() {
+        let wakers = CLOCK.with(|clock| {
+            let mut clock = clock.borrow_mut();
+            let wall = clock.system_now().duration_since(std::time::UNIX_EPOCH);
+            let wall = wall.unwrap_or_default();
+            let frozen = clock.frozen.take().expect("time is not frozen");
+            let millis = host::now();
+            clock.offset = frozen.0.as_secs_f64() * 1000. - millis;
+            // the wall clock carries on from where it was frozen too
+            clock.paused_wall = None;
+            clock.wall_offset = wall.as_secs_f64() * 1000. - host::date_now();
+            // pending sleeps go back to waiting on real timers
+            std::mem::take(&mut clock.timers)
+                .into_values()
+                .collect::<Vec<_>>()
+        });
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    pub async fn advance(duration: Duration) {
+        let wakers = CLOCK.with(|clock| {
+            let mut clock = clock.borrow_mut();
+            let frozen = clock.frozen.expect("time is not frozen");
+            clock.frozen = Some(frozen + duration);
+            clock.take_due_timers()
+        });
+        for waker in wakers {
+            waker.wake();
+        }
+        // give the woken tasks a chance to run
+        host::yield_now().await;
+    }
+
+    // moves the wall clock to `time` without touching `Instant`, like a manual
+    // clock change or an NTP correction would
+    #[cfg(feature = "test-util")]
+    pub fn set_system_time(time: SystemTime) {
+        CLOCK.with(|clock| {
+            let mut clock = clock.borrow_mut();
+            if clock.paused_wall.is_some() {
+                let now = clock.now();
+                clock.paused_wall = Some((time.0, now));
+            } else {
+                let wall = time.0.duration_since(std::time::UNIX_EPOCH);
+                let wall = wall.unwrap_or_default();
+                clock.wall_offset = wall.as_secs_f64() * 1000. - host::date_now();
+            }
+        });
+    }
+
+    pub fn interval(period: Duration) -> Interval {
+        interval_at(Instant::now(), period)
+    }
+
+    pub fn interval_at(start: Instant, period: Duration) -> Interval {
+        assert!(period > Duration::ZERO, "`period` must be non-zero.");
+        Interval {
+            deadline: start,
+            period,
+            missed_tick_behavior: MissedTickBehavior::default(),
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Interval {
+        deadline: Instant,
+        period: Duration,
+        missed_tick_behavior: MissedTickBehavior,
+    }
+
+    impl Interval {
+        pub async fn tick(&mut self) -> Instant {
+            sleep_until(self.deadline).await;
+            let timeout = self.deadline;
+            let now = Instant::now();
+            // same tolerance tokio uses before it considers a tick missed
+            self.deadline = if now > timeout + Duration::from_millis(5) {
+                match self.missed_tick_behavior {
+                    MissedTickBehavior::Burst => timeout + self.period,
+                    MissedTickBehavior::Delay => now + self.period,
+                    MissedTickBehavior::Skip => {
+                        let behind = (now - timeout).as_nanos() % self.period.as_nanos();
+                        now + self.period - Duration::from_nanos(behind as u64)
+                    }
+                }
+            } else {
+                timeout + self.period
+            };
+            timeout
+        }
+
+        pub fn reset(&mut self) {
+            self.deadline = Instant::now() + self.period;
+        }
+
+        pub fn period(&self) -> Duration {
+            self.period
+        }
+
+        pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+            self.missed_tick_behavior
+        }
+
+        pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+            self.missed_tick_behavior = behavior;
+        }
+    }
+
+    // the browser paces animation frames to the display's refresh rate
+    #[cfg(target_arch = "wasm32")]
+    pub fn set_frame_rate(_frames_per_second: u32) {}
+
+    pub async fn animation_frame() -> FrameTime {
+        let state = Rc::new(RefCell::new(FrameState {
+            timestamp: None,
+            waker: None,
+        }));
+        let callback = {
+            let state = state.clone();
+            move |timestamp| {
+                let waker = {
+                    let mut state = state.borrow_mut();
+                    state.timestamp = Some(timestamp);
+                    state.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+        };
+        let millis = AnimationFrame {
+            state,
+            _request: host::request_animation_frame(callback),
+        }
+        .await;
+        // the timestamp is on the page's clock, so shift it like `Instant::now`
+        FrameTime(CLOCK.with(|clock| clock.borrow().instant_at(millis)))
+    }
+
+    // cancels its `requestAnimationFrame` when dropped
+    struct AnimationFrame {
+        state: Rc<RefCell<FrameState>>,
+        _request: host::FrameRequest,
+    }
+
+    struct FrameState {
+        timestamp: Option<f64>,
+        waker: Option<Waker>,
+    }
+
+    impl Future for AnimationFrame {
+        type Output = f64;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<f64> {
+            let mut state = self.state.borrow_mut();
+            if let Some(timestamp) = state.timestamp {
+                return Poll::Ready(timestamp);
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub async fn idle() -> IdleDeadline {
+        let window = window().unwrap();
+        let deadline = JsFuture::from(Promise::new(&mut |resolve, _reject| {
+            window.request_idle_callback(&resolve).unwrap();
         }))
         .await
         .unwrap();
+        IdleDeadline(deadline.unchecked_into())
     }
+
+    #[cfg(target_arch = "wasm32")]
+    #[derive(Debug)]
+    pub struct IdleDeadline(web_sys::IdleDeadline);
+
+    #[cfg(target_arch = "wasm32")]
+    impl IdleDeadline {
+        pub fn time_remaining(&self) -> Duration {
+            Duration::from_secs_f64(self.0.time_remaining().max(0.) / 1000.)
+        }
+    }
+
+    frame_time!();
+    system_time!();
 }
 #[cfg(target_arch = "wasm32")]
 pub use wasm::*;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "wasm-sim"))]
+pub mod sim {
+    pub use super::wasm::*;
+}
+
+#[cfg(target_arch = "wasm32")]
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    #[default]
+    Burst,
+    Delay,
+    Skip,
+}
+
+pub fn animation_frames() -> AnimationFrames {
+    AnimationFrames { frame: None }
+}
+
+pub struct AnimationFrames {
+    frame: Option<BoxFuture<FrameTime>>,
+}
+
+impl AnimationFrames {
+    pub async fn next(&mut self) -> FrameTime {
+        std::future::poll_fn(|cx| self.poll_frame(cx)).await
+    }
+
+    fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<FrameTime> {
+        let frame = self
+            .frame
+            .get_or_insert_with(|| Box::pin(animation_frame()));
+        let time = std::task::ready!(frame.as_mut().poll(cx));
+        self.frame = None;
+        Poll::Ready(time)
+    }
+}
+
+impl Stream for AnimationFrames {
+    type Item = FrameTime;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<FrameTime>> {
+        self.poll_frame(cx).map(Some)
+    }
+}
+
+pub fn debounce<S: Stream + Unpin>(stream: S, duration: Duration) -> Debounce<S> {
+    Debounce {
+        stream,
+        duration,
+        pending: None,
+        deadline: None,
+        timer: None,
+        finished: false,
+    }
+}
+
+// yields an item once the stream has been quiet for `duration`, dropping
+// any items that were superseded before then
+pub struct Debounce<S: Stream> {
+    stream: S,
+    duration: Duration,
+    pending: Option<S::Item>,
+    // new items only push the deadline back. the timer is replaced once it
+    // fires before the deadline, so a burst of items (like pointer moves)
+    // costs one allocation per `duration` rather than one per item
+    deadline: Option<Instant>,
+    timer: Option<BoxFuture<()>>,
+    finished: bool,
+}
+
+impl<S: Stream + Unpin> Unpin for Debounce<S> {}
+
+impl<S: Stream + Unpin> Debounce<S> {
+    pub async fn next(&mut self) -> Option<S::Item> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<S: Stream + Unpin> Stream for Debounce<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        while !self.finished {
+            match Pin::new(&mut self.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    self.pending = Some(item);
+                    self.deadline = Some(Instant::now() + self.duration);
+                }
+                Poll::Ready(None) => self.finished = true,
+                Poll::Pending => break,
+            }
+        }
+        if self.finished {
+            self.deadline = None;
+            self.timer = None;
+            return Poll::Ready(self.pending.take());
+        }
+        while let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                self.deadline = None;
+                self.timer = None;
+                return Poll::Ready(self.pending.take());
+            }
+            let timer = self
+                .timer
+                .get_or_insert_with(|| Box::pin(sleep_until(deadline)));
+            std::task::ready!(timer.as_mut().poll(cx));
+            // it was armed for a deadline that has since been pushed back
+            self.timer = None;
+        }
+        Poll::Pending
+    }
+}
+
+pub fn throttle<S: Stream + Unpin>(stream: S, duration: Duration) -> Throttle<S> {
+    Throttle {
+        stream,
+        duration,
+        pending: None,
+        timer: None,
+        finished: false,
+    }
+}
+
+// yields at most one item per `duration`: the first item immediately, then
+// the latest item received while waiting once the duration has passed
+pub struct Throttle<S: Stream> {
+    stream: S,
+    duration: Duration,
+    pending: Option<S::Item>,
+    timer: Option<BoxFuture<()>>,
+    finished: bool,
+}
+
+impl<S: Stream + Unpin> Unpin for Throttle<S> {}
+
+impl<S: Stream + Unpin> Throttle<S> {
+    pub async fn next(&mut self) -> Option<S::Item> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<S: Stream + Unpin> Stream for Throttle<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        while !self.finished {
+            match Pin::new(&mut self.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    if self.timer.is_none() {
+                        self.timer = Some(Box::pin(sleep(self.duration)));
+                        return Poll::Ready(Some(item));
+                    }
+                    self.pending = Some(item);
+                }
+                Poll::Ready(None) => self.finished = true,
+                Poll::Pending => break,
+            }
+        }
+        if self.finished && self.pending.is_none() {
+            self.timer = None;
+            return Poll::Ready(None);
+        }
+        // an item left over when the stream ends still waits for its turn
+        if let Some(timer) = &mut self.timer {
+            std::task::ready!(timer.as_mut().poll(cx));
+            self.timer = None;
+            if let Some(item) = self.pending.take() {
+                if !self.finished {
+                    self.timer = Some(Box::pin(sleep(self.duration)));
+                }
+                return Poll::Ready(Some(item));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    initial_delay: Duration,
+    multiplier: f64,
+    max_delay: Duration,
+    jitter: f64,
+    max_attempts: Option<usize>,
+    max_elapsed: Option<Duration>,
+}
+
+impl RetryPolicy {
+    pub fn exponential(initial_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            multiplier: 2.,
+            max_delay: Duration::MAX,
+            jitter: 0.,
+            max_attempts: None,
+            max_elapsed: None,
+        }
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        assert!(multiplier >= 1., "`multiplier` must be at least 1.");
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    // randomly shortens each delay by up to this fraction of itself
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        assert!(
+            (0. ..=1.).contains(&jitter),
+            "`jitter` must be between 0 and 1."
+        );
+        self.jitter = jitter;
+        self
+    }
+
+    // counts the first attempt, so `max_attempts(1)` never retries
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        assert!(max_attempts > 0, "`max_attempts` must be non-zero.");
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    // gives up instead of sleeping past this long after the first attempt
+    pub fn max_elapsed(mut self, max_elapsed: Duration) -> Self {
+        self.max_elapsed = Some(max_elapsed);
+        self
+    }
+}
+
+pub async fn retry<T, E, F, Fut>(policy: RetryPolicy, operation: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry_if(policy, operation, |_| true).await
+}
+
+pub async fn retry_if<T, E, F, Fut, P>(
+    policy: RetryPolicy,
+    mut operation: F,
+    mut retryable: P,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: FnMut(&E) -> bool,
+{
+    let start = Instant::now();
+    let mut delay = policy.initial_delay.min(policy.max_delay);
+    let mut attempts = 0;
+    loop {
+        let error = match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        attempts += 1;
+        if !retryable(&error) || policy.max_attempts.is_some_and(|max| attempts >= max) {
+            return Err(error);
+        }
+        let wait = delay.mul_f64(1. - policy.jitter * random());
+        if policy
+            .max_elapsed
+            .is_some_and(|max_elapsed| start.elapsed().saturating_add(wait) > max_elapsed)
+        {
+            return Err(error);
+        }
+        sleep(wait).await;
+        delay = Duration::try_from_secs_f64(delay.as_secs_f64() * policy.multiplier)
+            .unwrap_or(policy.max_delay)
+            .min(policy.max_delay);
+    }
+}
+
 pub async fn sleep_forever() -> ! {
     loop {
         sleep(Duration::from_secs(22896000)).await;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures_core::Stream;
+
+use super::{sleep_until, BoxFuture, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key(u64);
+
+#[derive(Debug)]
+pub struct Expired<T> {
+    key: Key,
+    deadline: Instant,
+    value: T,
+}
+
+impl<T> Expired<T> {
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+// all entries share a single timer, which always targets the earliest deadline
+pub struct DelayQueue<T> {
+    entries: HashMap<Key, (T, Instant)>,
+    expirations: BTreeSet<(Instant, Key)>,
+    next_key: u64,
+    timer: Option<(Instant, BoxFuture<()>)>,
+    // the task waiting on `timer`, which has to be woken to re-target it when
+    // an earlier deadline comes in
+    waker: Option<Waker>,
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            expirations: BTreeSet::new(),
+            next_key: 0,
+            timer: None,
+            waker: None,
+        }
+    }
+}
+
+impl<T> Unpin for DelayQueue<T> {}
+
+impl<T> DelayQueue<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, value: T, timeout: Duration) -> Key {
+        self.insert_at(value, Instant::now() + timeout)
+    }
+
+    pub fn insert_at(&mut self, value: T, deadline: Instant) -> Key {
+        let key = Key(self.next_key);
+        self.next_key += 1;
+        self.entries.insert(key, (value, deadline));
+        self.expirations.insert((deadline, key));
+        self.wake_if_earlier(deadline);
+        key
+    }
+
+    pub fn remove(&mut self, key: &Key) -> Option<T> {
+        let (value, deadline) = self.entries.remove(key)?;
+        self.expirations.remove(&(deadline, *key));
+        Some(value)
+    }
+
+    pub fn reset(&mut self, key: &Key, timeout: Duration) -> bool {
+        self.reset_at(key, Instant::now() + timeout)
+    }
+
+    pub fn reset_at(&mut self, key: &Key, deadline: Instant) -> bool {
+        let Some((_, entry_deadline)) = self.entries.get_mut(key) else {
+            return false;
+        };
+        self.expirations.remove(&(*entry_deadline, *key));
+        self.expirations.insert((deadline, *key));
+        *entry_deadline = deadline;
+        self.wake_if_earlier(deadline);
+        true
+    }
+
+    fn wake_if_earlier(&mut self, deadline: Instant) {
+        if matches!(&self.timer, Some((timer_deadline, _)) if deadline < *timer_deadline) {
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    pub fn deadline(&self, key: &Key) -> Option<Instant> {
+        self.entries.get(key).map(|(_, deadline)| *deadline)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.expirations.clear();
+        self.timer = None;
+        // a pending poll was waiting on the timer, and now resolves to `None`
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    // resolves to `None` if the queue is empty
+    pub async fn next_expired(&mut self) -> Option<Expired<T>> {
+        std::future::poll_fn(|cx| self.poll_expired(cx)).await
+    }
+
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Option<Expired<T>>> {
+        loop {
+            let Some(&(deadline, key)) = self.expirations.first() else {
+                self.timer = None;
+                return Poll::Ready(None);
+            };
+            if deadline <= Instant::now() {
+                self.expirations.pop_first();
+                let (value, _) = self.entries.remove(&key).unwrap();
+                return Poll::Ready(Some(Expired {
+                    key,
+                    deadline,
+                    value,
+                }));
+            }
+            let timer = match &mut self.timer {
+                Some((timer_deadline, timer)) if *timer_deadline == deadline => timer,
+                timer => &mut timer.insert((deadline, Box::pin(sleep_until(deadline)))).1,
+            };
+            if timer.as_mut().poll(cx).is_pending() {
+                match &mut self.waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    waker => *waker = Some(cx.waker().clone()),
+                }
+                return Poll::Pending;
+            }
+            self.timer = None;
+        }
+    }
+}
+
+impl<T> Stream for DelayQueue<T> {
+    type Item = Expired<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Expired<T>>> {
+        self.poll_expired(cx)
+    }
+}
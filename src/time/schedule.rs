@@ -0,0 +1,377 @@
+use std::{future::Future, str::FromStr, time::Duration};
+
+use super::{sleep, SystemTime, UNIX_EPOCH};
+
+// how long to sleep at most before checking the wall clock again, so that
+// clock jumps (suspend, NTP corrections, manual changes) are noticed
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+// a five field cron expression (minute, hour, day of month, month, day of
+// week). fields are always matched against UTC, not the local time zone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Self, ParseError> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(ParseError(format!(
+                "expected 5 fields, found {}",
+                fields.len()
+            )));
+        };
+        let any_day_of_month = days_of_month.starts_with('*');
+        let any_day_of_week = days_of_week.starts_with('*');
+        let mut days_of_week = parse_field(days_of_week, 0, 7, &DAY_NAMES)?;
+        // both 0 and 7 mean sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59, &[])?,
+            hours: parse_field(hours, 0, 23, &[])?,
+            days_of_month: parse_field(days_of_month, 1, 31, &[])?,
+            months: parse_field(months, 1, 12, &MONTH_NAMES)?,
+            days_of_week,
+            any_day_of_month,
+            any_day_of_week,
+        })
+    }
+
+    // the first matching minute strictly after `after`, or `None` if the
+    // expression can never match
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let after = after.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut minute = after.as_secs() / 60 + 1;
+        let (start_year, _, _) = civil_from_days(minute / 1440);
+        loop {
+            let days = minute / 1440;
+            let (year, month, day) = civil_from_days(days);
+            // every combination of leap year and weekday repeats within 28 years
+            if year > start_year + 28 {
+                return None;
+            }
+            if self.months & (1 << month) == 0 {
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                minute = days_from_civil(year, month, 1) * 1440;
+                continue;
+            }
+            if !self.matches_day(day, (days + 4) % 7) {
+                minute = (days + 1) * 1440;
+                continue;
+            }
+            if self.hours & (1 << (minute / 60 % 24)) == 0 {
+                minute = (minute / 60 + 1) * 60;
+                continue;
+            }
+            if self.minutes & (1 << (minute % 60)) == 0 {
+                minute += 1;
+                continue;
+            }
+            return Some(UNIX_EPOCH + Duration::from_secs(minute * 60));
+        }
+    }
+
+    fn matches_day(&self, day_of_month: u64, day_of_week: u64) -> bool {
+        let day_of_month = self.days_of_month & (1 << day_of_month) != 0;
+        let day_of_week = self.days_of_week & (1 << day_of_week) != 0;
+        // like vixie cron, restricting both fields matches either of them. a
+        // field starting with `*` (including a stepped one like `*/2`) only
+        // narrows the other one down
+        if self.any_day_of_month || self.any_day_of_week {
+            day_of_month && day_of_week
+        } else {
+            day_of_month || day_of_week
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ParseError;
+
+    fn from_str(expression: &str) -> Result<Self, ParseError> {
+        Self::parse(expression)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn<F, Fut>(schedule: Schedule, job: F)
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    crate::spawn(run(schedule, job));
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn spawn<F, Fut>(schedule: Schedule, job: F)
+where
+    F: FnMut() -> Fut + 'static,
+    Fut: Future<Output = ()> + 'static,
+{
+    crate::spawn(run(schedule, job));
+}
+
+// runs `job` at every minute matching `schedule`, in UTC, waiting for each
+// run to finish before looking for the next one
+pub async fn run<F, Fut>(schedule: Schedule, mut job: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut last_run: Option<SystemTime> = None;
+    loop {
+        // never run the same minute twice if the clock jumps backwards, and
+        // run missed minutes only once if it jumps forwards
+        let now = SystemTime::now();
+        let after = last_run.map_or(now, |last_run| last_run.max(now));
+        let Some(next_run) = schedule.next_after(after) else {
+            return;
+        };
+        while let Ok(remaining) = next_run.duration_since(SystemTime::now()) {
+            if remaining.is_zero() {
+                break;
+            }
+            sleep(remaining.min(MAX_SLEEP)).await;
+        }
+        last_run = Some(next_run);
+        job().await;
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError(String);
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "invalid cron expression: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn parse_field(field: &str, min: u64, max: u64, names: &[&str]) -> Result<u64, ParseError> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            // bounded by the field, so it can't overflow `usize` on wasm32
+            Some((range, step)) => (range, Some(parse_value(step, 1, max, &[])?)),
+            None => (part, None),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, max, names)?,
+                parse_value(end, min, max, names)?,
+            )
+        } else {
+            let start = parse_value(range, min, max, names)?;
+            // `5/15` means every 15 starting at 5
+            (start, if step.is_some() { max } else { start })
+        };
+        if start > end {
+            return Err(ParseError(format!("invalid range `{}`", range)));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(value: &str, min: u64, max: u64, names: &[&str]) -> Result<u64, ParseError> {
+    let lowercase = value.to_ascii_lowercase();
+    let parsed = match names.iter().position(|name| *name == lowercase) {
+        // names start at the field's minimum (january is 1, sunday is 0)
+        Some(index) => index as u64 + min,
+        None => value
+            .parse()
+            .map_err(|_| ParseError(format!("invalid value `{}`", value)))?,
+    };
+    if parsed < min || parsed > max {
+        return Err(ParseError(format!(
+            "`{}` is outside of {}-{}",
+            value, min, max
+        )));
+    }
+    Ok(parsed)
+}
+
+// date conversions from http://howardhinnant.github.io/date_algorithms.html,
+// restricted to dates after the unix epoch
+
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: u64, month: u64, day: u64, hour: u64, minute: u64) -> SystemTime {
+        let minutes = days_from_civil(year, month, day) * 1440 + hour * 60 + minute;
+        UNIX_EPOCH + Duration::from_secs(minutes * 60)
+    }
+
+    fn next(expression: &str, after: SystemTime) -> Option<SystemTime> {
+        Schedule::parse(expression).unwrap().next_after(after)
+    }
+
+    #[test]
+    fn steps_through_minutes() {
+        let mut time = at(2026, 1, 1, 0, 0);
+        for (hour, minute) in [(0, 15), (0, 30), (0, 45), (1, 0)] {
+            time = next("*/15 * * * *", time).unwrap();
+            assert_eq!(time, at(2026, 1, 1, hour, minute));
+        }
+        assert_eq!(
+            next("5/20 * * * *", at(2026, 1, 1, 0, 30)),
+            Some(at(2026, 1, 1, 0, 45))
+        );
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // 2026-01-02 and 2026-01-09 are fridays, 2026-01-13 is a tuesday
+        let schedule = "0 0 13 * fri";
+        assert_eq!(
+            next(schedule, at(2026, 1, 1, 0, 0)),
+            Some(at(2026, 1, 2, 0, 0))
+        );
+        assert_eq!(
+            next(schedule, at(2026, 1, 9, 0, 0)),
+            Some(at(2026, 1, 13, 0, 0))
+        );
+        // only one restricted field has to match on its own
+        assert_eq!(
+            next("0 0 13 * *", at(2026, 1, 1, 0, 0)),
+            Some(at(2026, 1, 13, 0, 0))
+        );
+        assert_eq!(
+            next("0 0 * * fri", at(2026, 1, 9, 0, 0)),
+            Some(at(2026, 1, 16, 0, 0))
+        );
+    }
+
+    #[test]
+    fn stepped_day_fields_keep_their_step() {
+        assert_eq!(
+            next("0 0 */2 * *", at(2026, 1, 1, 0, 0)),
+            Some(at(2026, 1, 3, 0, 0))
+        );
+        // 2026-01-12 is a monday, but only odd days of the month match
+        assert_eq!(
+            next("0 0 */2 * mon", at(2026, 1, 6, 0, 0)),
+            Some(at(2026, 1, 19, 0, 0))
+        );
+        assert_eq!(
+            next("*/10 * */10 * *", at(2026, 1, 1, 0, 0)),
+            Some(at(2026, 1, 1, 0, 10))
+        );
+        assert_eq!(
+            next("*/10 * */10 * *", at(2026, 1, 1, 23, 50)),
+            Some(at(2026, 1, 11, 0, 0))
+        );
+    }
+
+    #[test]
+    fn sunday_is_zero_or_seven() {
+        let sunday = Schedule::parse("0 0 * * 0").unwrap();
+        assert_eq!(Schedule::parse("0 0 * * 7").unwrap(), sunday);
+        assert_eq!(Schedule::parse("0 0 * * SUN").unwrap(), sunday);
+        assert_eq!(Schedule::parse("@weekly").unwrap(), sunday);
+        // 2026-10-18 is a sunday
+        assert_eq!(
+            sunday.next_after(at(2026, 10, 14, 12, 0)),
+            Some(at(2026, 10, 18, 0, 0))
+        );
+        assert_eq!(
+            Schedule::parse("0 0 * * 5-7").unwrap(),
+            Schedule::parse("0 0 * * fri,sat,sun").unwrap()
+        );
+    }
+
+    #[test]
+    fn rare_and_impossible_dates() {
+        assert_eq!(
+            next("0 0 29 2 *", at(2026, 1, 1, 0, 0)),
+            Some(at(2028, 2, 29, 0, 0))
+        );
+        assert_eq!(next("0 0 31 feb *", at(2026, 1, 1, 0, 0)), None);
+        assert_eq!(
+            next("0 0 31 * *", at(2026, 4, 1, 0, 0)),
+            Some(at(2026, 5, 31, 0, 0))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in [
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "* * * foo *",
+            "30-10 * * * *",
+            "*/0 * * * *",
+            "*/61 * * * *",
+            "*/4294967296 * * * *",
+            "*/x * * * *",
+        ] {
+            assert!(Schedule::parse(expression).is_err(), "{expression} parsed");
+        }
+    }
+}
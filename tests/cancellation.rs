@@ -0,0 +1,243 @@
+// Drops the wasm primitives' futures while they wait and checks that nothing
+// leaks: no lost wakeups, no stuck lock counts, no missing permits.
+#![cfg(feature = "wasm-sim")]
+
+use std::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use tokasm::sync::sim::{mpsc, Mutex, Notify, RwLock, Semaphore};
+
+fn poll_once<F: Future>(future: std::pin::Pin<&mut F>) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(Waker::noop()))
+}
+
+fn is_ready<F: Future>(future: F) -> bool {
+    poll_once(pin!(future)).is_ready()
+}
+
+#[test]
+fn notify_one_skips_dropped_waiter() {
+    let notify = Notify::new();
+    let mut first = Box::pin(notify.notified());
+    let mut second = Box::pin(notify.notified());
+    assert!(poll_once(first.as_mut()).is_pending());
+    assert!(poll_once(second.as_mut()).is_pending());
+    drop(first);
+    notify.notify_one();
+    assert!(poll_once(second.as_mut()).is_ready());
+}
+
+#[test]
+fn dropped_waiter_passes_on_notify_one() {
+    let notify = Notify::new();
+    let mut first = Box::pin(notify.notified());
+    let mut second = Box::pin(notify.notified());
+    assert!(poll_once(first.as_mut()).is_pending());
+    assert!(poll_once(second.as_mut()).is_pending());
+    notify.notify_one();
+    drop(first);
+    assert!(poll_once(second.as_mut()).is_ready());
+}
+
+#[test]
+fn dropped_last_waiter_keeps_permit() {
+    let notify = Notify::new();
+    let mut waiter = Box::pin(notify.notified());
+    assert!(poll_once(waiter.as_mut()).is_pending());
+    notify.notify_one();
+    drop(waiter);
+    assert!(is_ready(notify.notified()));
+    assert!(!is_ready(notify.notified()));
+}
+
+#[test]
+fn dropped_waiter_does_not_keep_notify_waiters() {
+    let notify = Notify::new();
+    let mut waiter = Box::pin(notify.notified());
+    assert!(poll_once(waiter.as_mut()).is_pending());
+    notify.notify_waiters();
+    drop(waiter);
+    assert!(!is_ready(notify.notified()));
+}
+
+#[test]
+fn mutex_lock_dropped_while_waiting() {
+    let mutex = Mutex::new(0);
+    let guard = mutex.try_lock().unwrap();
+    for _ in 0..3 {
+        let mut lock = Box::pin(mutex.lock());
+        assert!(poll_once(lock.as_mut()).is_pending());
+    }
+    drop(guard);
+    assert!(mutex.try_lock().is_ok());
+}
+
+#[test]
+fn mutex_lock_dropped_after_handoff() {
+    let mutex = Mutex::new(0);
+    let guard = mutex.try_lock().unwrap();
+    let mut first = Box::pin(mutex.lock());
+    let mut second = Box::pin(mutex.lock());
+    assert!(poll_once(first.as_mut()).is_pending());
+    assert!(poll_once(second.as_mut()).is_pending());
+    drop(guard);
+    drop(first);
+    let Poll::Ready(guard) = poll_once(second.as_mut()) else {
+        panic!("lock was not passed on");
+    };
+    drop(guard);
+    drop(second);
+    assert!(mutex.try_lock().is_ok());
+}
+
+#[test]
+fn mutex_every_waiter_dropped_in_turn() {
+    for dropped in 0..4 {
+        let mutex = Mutex::new(0);
+        let guard = mutex.try_lock().unwrap();
+        let mut waiters: Vec<_> = (0..4).map(|_| Box::pin(mutex.lock())).collect();
+        for waiter in &mut waiters {
+            assert!(poll_once(waiter.as_mut()).is_pending());
+        }
+        drop(waiters.remove(dropped));
+        drop(guard);
+        for mut waiter in waiters {
+            let Poll::Ready(mut guard) = poll_once(waiter.as_mut()) else {
+                panic!("waiter was skipped");
+            };
+            *guard += 1;
+        }
+        assert_eq!(*mutex.try_lock().unwrap(), 3);
+    }
+}
+
+#[test]
+fn rwlock_write_dropped_while_waiting() {
+    let lock = RwLock::new(0);
+    let mut read = Box::pin(lock.read());
+    let Poll::Ready(guard) = poll_once(read.as_mut()) else {
+        panic!("uncontended read should not wait");
+    };
+    let mut write = Box::pin(lock.write());
+    assert!(poll_once(write.as_mut()).is_pending());
+    drop(write);
+    assert!(is_ready(lock.read()));
+    drop(guard);
+    drop(read);
+    assert!(is_ready(lock.write()));
+}
+
+#[test]
+fn rwlock_write_dropped_after_handoff() {
+    let lock = RwLock::new(0);
+    let mut first = Box::pin(lock.write());
+    let Poll::Ready(guard) = poll_once(first.as_mut()) else {
+        panic!("uncontended write should not wait");
+    };
+    let mut second = Box::pin(lock.write());
+    assert!(poll_once(second.as_mut()).is_pending());
+    drop(guard);
+    drop(second);
+    drop(first);
+    assert!(is_ready(lock.write()));
+    assert!(is_ready(lock.read()));
+}
+
+#[test]
+fn rwlock_read_dropped_while_waiting() {
+    let lock = RwLock::new(0);
+    let mut write = Box::pin(lock.write());
+    let Poll::Ready(guard) = poll_once(write.as_mut()) else {
+        panic!("uncontended write should not wait");
+    };
+    let mut read = Box::pin(lock.read());
+    assert!(poll_once(read.as_mut()).is_pending());
+    drop(read);
+    drop(guard);
+    drop(write);
+    assert!(is_ready(lock.write()));
+}
+
+#[test]
+fn rwlock_read_dropped_after_handoff() {
+    let lock = RwLock::new(0);
+    let mut write = Box::pin(lock.write());
+    let Poll::Ready(guard) = poll_once(write.as_mut()) else {
+        panic!("uncontended write should not wait");
+    };
+    let mut read = Box::pin(lock.read());
+    assert!(poll_once(read.as_mut()).is_pending());
+    drop(guard);
+    drop(read);
+    drop(write);
+    assert!(is_ready(lock.write()));
+}
+
+#[test]
+fn semaphore_returns_partial_assignment() {
+    let semaphore = Semaphore::new(1);
+    let mut acquire = Box::pin(semaphore.acquire_many(3));
+    assert!(poll_once(acquire.as_mut()).is_pending());
+    assert_eq!(semaphore.available_permits(), 0);
+    drop(acquire);
+    assert_eq!(semaphore.available_permits(), 1);
+}
+
+#[test]
+fn semaphore_passes_on_dropped_assignment() {
+    let semaphore = Semaphore::new(0);
+    let mut first = Box::pin(semaphore.acquire_many(2));
+    let mut second = Box::pin(semaphore.acquire());
+    assert!(poll_once(first.as_mut()).is_pending());
+    assert!(poll_once(second.as_mut()).is_pending());
+    semaphore.add_permits(2);
+    drop(first);
+    let Poll::Ready(Ok(permit)) = poll_once(second.as_mut()) else {
+        panic!("permits were not passed on");
+    };
+    assert_eq!(semaphore.available_permits(), 1);
+    drop(permit);
+    assert_eq!(semaphore.available_permits(), 2);
+}
+
+#[test]
+fn semaphore_every_waiter_dropped_in_turn() {
+    for dropped in 0..4 {
+        let semaphore = Semaphore::new(0);
+        let mut waiters: Vec<_> = (1..=4)
+            .map(|n| Box::pin(semaphore.acquire_many(n)))
+            .collect();
+        for waiter in &mut waiters {
+            assert!(poll_once(waiter.as_mut()).is_pending());
+        }
+        semaphore.add_permits(3);
+        drop(waiters.remove(dropped));
+        semaphore.add_permits(7);
+        let mut acquired = 0;
+        for mut waiter in waiters {
+            let Poll::Ready(Ok(permit)) = poll_once(waiter.as_mut()) else {
+                panic!("waiter was skipped");
+            };
+            acquired += permit.num_permits();
+            permit.forget();
+        }
+        assert_eq!(acquired + semaphore.available_permits(), 10);
+    }
+}
+
+#[test]
+fn mpsc_send_dropped_while_full() {
+    let (sender, mut receiver) = mpsc::channel(1);
+    assert!(is_ready(sender.send(1)));
+    let mut send = Box::pin(sender.send(2));
+    assert!(poll_once(send.as_mut()).is_pending());
+    drop(send);
+    assert_eq!(receiver.try_recv(), Ok(1));
+    assert!(sender.try_send(3).is_ok());
+    assert_eq!(sender.capacity(), 0);
+    assert_eq!(receiver.try_recv(), Ok(3));
+    assert_eq!(sender.capacity(), 1);
+}
@@ -0,0 +1,734 @@
+// The same checks run against the tokio wrappers and, with the `wasm-sim`
+// feature, against the wasm implementations, so the two can't drift apart.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+fn poll_once<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(Waker::noop()))
+}
+
+fn ready<F: Future>(future: Pin<&mut F>) -> F::Output {
+    match poll_once(future) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future was not ready"),
+    }
+}
+
+macro_rules! conformance {
+    ($backend:ident, $($sync:tt)*) => {
+        mod $backend {
+            use super::{poll_once, ready};
+            use $($sync)*::{
+                broadcast, mpsc, oneshot, watch, AcquireError, Mutex, Notify, OnceCell,
+                OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock, RwLockReadGuard,
+                RwLockWriteGuard, Semaphore, SetError, TryAcquireError,
+            };
+
+            #[test]
+            fn notify_one_stores_a_single_permit() {
+                let notify = Notify::new();
+                notify.notify_one();
+                notify.notify_one();
+                let mut first = Box::pin(notify.notified());
+                let mut second = Box::pin(notify.notified());
+                assert!(poll_once(first.as_mut()).is_ready());
+                assert!(poll_once(second.as_mut()).is_pending());
+            }
+
+            #[test]
+            fn notify_one_wakes_waiters_in_order() {
+                let notify = Notify::new();
+                let mut waiters: Vec<_> = (0..3).map(|_| Box::pin(notify.notified())).collect();
+                for waiter in &mut waiters {
+                    assert!(poll_once(waiter.as_mut()).is_pending());
+                }
+                for woken in 0..3 {
+                    notify.notify_one();
+                    for (index, waiter) in waiters.iter_mut().enumerate().skip(woken) {
+                        assert_eq!(poll_once(waiter.as_mut()).is_ready(), index == woken);
+                    }
+                }
+            }
+
+            #[test]
+            fn notify_waiters_stores_no_permit() {
+                let notify = Notify::new();
+                let mut waiters: Vec<_> = (0..3).map(|_| Box::pin(notify.notified())).collect();
+                for waiter in &mut waiters {
+                    assert!(poll_once(waiter.as_mut()).is_pending());
+                }
+                notify.notify_waiters();
+                for waiter in &mut waiters {
+                    assert!(poll_once(waiter.as_mut()).is_ready());
+                }
+                let mut late = Box::pin(notify.notified());
+                assert!(poll_once(late.as_mut()).is_pending());
+            }
+
+            #[test]
+            fn notify_one_goes_to_enabled_waiter() {
+                let notify = Notify::new();
+                let mut idle = Box::pin(notify.notified());
+                let mut enabled = Box::pin(notify.notified());
+                assert!(!enabled.as_mut().enable());
+                notify.notify_one();
+                assert!(poll_once(idle.as_mut()).is_pending());
+                assert!(poll_once(enabled.as_mut()).is_ready());
+            }
+
+            #[test]
+            fn notify_waiters_reaches_unpolled_futures() {
+                let notify = Notify::new();
+                let mut created = Box::pin(notify.notified());
+                notify.notify_waiters();
+                let mut late = Box::pin(notify.notified());
+                assert!(created.as_mut().enable());
+                assert!(poll_once(late.as_mut()).is_pending());
+            }
+
+            #[test]
+            fn notify_last_wakes_newest_waiter() {
+                let notify = Notify::new();
+                let mut first = Box::pin(notify.notified());
+                let mut second = Box::pin(notify.notified());
+                assert!(poll_once(first.as_mut()).is_pending());
+                assert!(poll_once(second.as_mut()).is_pending());
+                notify.notify_last();
+                assert!(poll_once(first.as_mut()).is_pending());
+                assert!(poll_once(second.as_mut()).is_ready());
+            }
+
+            #[test]
+            fn owned_notified_outlives_borrow() {
+                let notify = std::sync::Arc::new(Notify::new());
+                let mut notified = Box::pin(notify.clone().notified_owned());
+                assert!(poll_once(notified.as_mut()).is_pending());
+                notify.notify_one();
+                assert!(poll_once(notified.as_mut()).is_ready());
+            }
+
+            #[test]
+            fn mpsc_drains_after_senders_drop() {
+                let (sender, mut receiver) = mpsc::channel(4);
+                let other = sender.clone();
+                assert_eq!(sender.try_send(1), Ok(()));
+                assert_eq!(other.try_send(2), Ok(()));
+                drop(sender);
+                assert_eq!(receiver.try_recv(), Ok(1));
+                drop(other);
+                assert_eq!(ready(Box::pin(receiver.recv()).as_mut()), Some(2));
+                assert_eq!(ready(Box::pin(receiver.recv()).as_mut()), None);
+                assert_eq!(receiver.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+            }
+
+            #[test]
+            fn mpsc_send_after_receiver_drop_returns_value() {
+                let (sender, receiver) = mpsc::channel(4);
+                let mut closed = Box::pin(sender.closed());
+                assert!(poll_once(closed.as_mut()).is_pending());
+                assert!(!sender.is_closed());
+                drop(receiver);
+                assert!(sender.is_closed());
+                assert!(poll_once(closed.as_mut()).is_ready());
+                assert_eq!(ready(Box::pin(sender.send(1)).as_mut()), Err(mpsc::SendError(1)));
+                assert_eq!(sender.try_send(2), Err(mpsc::TrySendError::Closed(2)));
+            }
+
+            #[test]
+            fn mpsc_close_keeps_buffered_values() {
+                let (sender, mut receiver) = mpsc::channel(4);
+                assert_eq!(sender.try_send(1), Ok(()));
+                receiver.close();
+                assert!(sender.is_closed());
+                assert_eq!(sender.try_send(2), Err(mpsc::TrySendError::Closed(2)));
+                assert_eq!(receiver.try_recv(), Ok(1));
+                assert_eq!(ready(Box::pin(receiver.recv()).as_mut()), None);
+            }
+
+            #[test]
+            fn mpsc_permit_reserved_before_close_still_sends() {
+                let (sender, mut receiver) = mpsc::channel(4);
+                let permit = ready(Box::pin(sender.reserve()).as_mut()).unwrap();
+                receiver.close();
+                assert!(sender.is_closed());
+                // the receiver waits for the outstanding permit
+                assert_eq!(receiver.try_recv(), Err(mpsc::TryRecvError::Empty));
+                let mut recv = Box::pin(receiver.recv());
+                assert!(poll_once(recv.as_mut()).is_pending());
+                permit.send(1);
+                assert_eq!(ready(recv.as_mut()), Some(1));
+                drop(recv);
+                assert_eq!(ready(Box::pin(receiver.recv()).as_mut()), None);
+                // an unused one doesn't hold the receiver up once it's dropped
+                let (sender, mut receiver) = mpsc::channel::<i32>(4);
+                let permit = ready(Box::pin(sender.reserve()).as_mut()).unwrap();
+                receiver.close();
+                let mut recv = Box::pin(receiver.recv());
+                assert!(poll_once(recv.as_mut()).is_pending());
+                drop(permit);
+                assert_eq!(ready(recv.as_mut()), None);
+            }
+
+            #[test]
+            fn mpsc_bounded_capacity() {
+                let (sender, mut receiver) = mpsc::channel(2);
+                assert_eq!(sender.max_capacity(), 2);
+                assert_eq!(sender.try_send(1), Ok(()));
+                assert_eq!(sender.try_send(2), Ok(()));
+                assert_eq!(sender.capacity(), 0);
+                assert_eq!(sender.try_send(3), Err(mpsc::TrySendError::Full(3)));
+                let mut send = Box::pin(sender.send(3));
+                assert!(poll_once(send.as_mut()).is_pending());
+                assert_eq!(receiver.try_recv(), Ok(1));
+                assert_eq!(ready(send.as_mut()), Ok(()));
+                let mut values = Vec::new();
+                assert_eq!(ready(Box::pin(receiver.recv_many(&mut values, 8)).as_mut()), 2);
+                assert_eq!(values, [2, 3]);
+                assert_eq!(sender.capacity(), 2);
+            }
+
+            #[test]
+            fn mpsc_weak_sender_does_not_keep_channel_open() {
+                let (sender, mut receiver) = mpsc::unbounded_channel::<i32>();
+                let weak = sender.downgrade();
+                assert!(weak.upgrade().is_some());
+                drop(sender);
+                assert!(weak.upgrade().is_none());
+                assert_eq!(ready(Box::pin(receiver.recv()).as_mut()), None);
+            }
+
+            #[test]
+            fn oneshot_sender_drop_fails_receiver() {
+                let (sender, mut receiver) = oneshot::channel::<i32>();
+                assert_eq!(receiver.try_recv(), Err(oneshot::TryRecvError::Empty));
+                assert!(poll_once(std::pin::Pin::new(&mut receiver)).is_pending());
+                drop(sender);
+                assert!(ready(std::pin::Pin::new(&mut receiver)).is_err());
+            }
+
+            #[test]
+            fn oneshot_receiver_drop_closes_sender() {
+                let (mut sender, receiver) = oneshot::channel();
+                assert!(!sender.is_closed());
+                let mut closed = Box::pin(async move {
+                    sender.closed().await;
+                    sender
+                });
+                assert!(poll_once(closed.as_mut()).is_pending());
+                drop(receiver);
+                let sender = ready(closed.as_mut());
+                assert!(sender.is_closed());
+                assert_eq!(sender.send(1), Err(1));
+            }
+
+            #[test]
+            fn oneshot_value_is_taken_once() {
+                let (sender, mut receiver) = oneshot::channel();
+                assert_eq!(sender.send(1), Ok(()));
+                assert_eq!(receiver.try_recv(), Ok(1));
+                assert_eq!(receiver.try_recv(), Err(oneshot::TryRecvError::Closed));
+            }
+
+            #[test]
+            fn oneshot_close_rejects_later_send() {
+                let (sender, mut receiver) = oneshot::channel();
+                receiver.close();
+                assert!(sender.is_closed());
+                assert_eq!(sender.send(1), Err(1));
+                assert!(ready(std::pin::Pin::new(&mut receiver)).is_err());
+            }
+
+            #[test]
+            fn broadcast_lagging_receiver_skips_oldest() {
+                let (sender, mut receiver) = broadcast::channel(2);
+                for value in 1..=4 {
+                    assert_eq!(sender.send(value), Ok(1));
+                }
+                assert_eq!(receiver.try_recv(), Err(broadcast::TryRecvError::Lagged(2)));
+                assert_eq!(receiver.try_recv(), Ok(3));
+                assert_eq!(receiver.try_recv(), Ok(4));
+                assert_eq!(receiver.try_recv(), Err(broadcast::TryRecvError::Empty));
+
+                for value in 5..=7 {
+                    assert_eq!(sender.send(value), Ok(1));
+                }
+                let mut recv = Box::pin(receiver.recv());
+                assert_eq!(ready(recv.as_mut()), Err(broadcast::RecvError::Lagged(1)));
+                drop(recv);
+                assert_eq!(ready(Box::pin(receiver.recv()).as_mut()), Ok(6));
+            }
+
+            #[test]
+            fn broadcast_closes_after_senders_drop() {
+                let (sender, mut receiver) = broadcast::channel(4);
+                let other = sender.clone();
+                assert_eq!(sender.send(1), Ok(1));
+                drop(sender);
+                assert_eq!(ready(Box::pin(receiver.recv()).as_mut()), Ok(1));
+                let mut recv = Box::pin(receiver.recv());
+                assert!(poll_once(recv.as_mut()).is_pending());
+                drop(other);
+                assert_eq!(ready(recv.as_mut()), Err(broadcast::RecvError::Closed));
+                drop(recv);
+                assert_eq!(receiver.try_recv(), Err(broadcast::TryRecvError::Closed));
+            }
+
+            #[test]
+            fn broadcast_send_without_receivers_fails() {
+                let (sender, receiver) = broadcast::channel(4);
+                let late = sender.subscribe();
+                drop(receiver);
+                assert_eq!(sender.receiver_count(), 1);
+                assert_eq!(sender.send(1), Ok(1));
+                drop(late);
+                assert_eq!(sender.send(2), Err(broadcast::SendError(2)));
+            }
+
+            #[test]
+            fn watch_changed_marks_value_seen() {
+                let (sender, mut receiver) = watch::channel(0);
+                assert_eq!(receiver.has_changed(), Ok(false));
+                let mut changed = Box::pin(receiver.changed());
+                assert!(poll_once(changed.as_mut()).is_pending());
+                assert_eq!(sender.send(1), Ok(()));
+                assert_eq!(ready(changed.as_mut()), Ok(()));
+                drop(changed);
+                assert_eq!(receiver.has_changed(), Ok(false));
+                assert_eq!(*receiver.borrow(), 1);
+
+                sender.send_modify(|value| *value += 1);
+                assert_eq!(receiver.has_changed(), Ok(true));
+                assert_eq!(*receiver.borrow(), 2);
+                assert_eq!(receiver.has_changed(), Ok(true));
+                assert_eq!(*receiver.borrow_and_update(), 2);
+                assert_eq!(receiver.has_changed(), Ok(false));
+                assert!(poll_once(Box::pin(receiver.changed()).as_mut()).is_pending());
+            }
+
+            #[test]
+            fn watch_closes_on_sender_drop() {
+                let (sender, mut receiver) = watch::channel(0);
+                let mut changed = Box::pin(receiver.changed());
+                assert!(poll_once(changed.as_mut()).is_pending());
+                drop(sender);
+                assert!(ready(changed.as_mut()).is_err());
+                drop(changed);
+                assert!(receiver.has_changed().is_err());
+                assert_eq!(*receiver.borrow(), 0);
+            }
+
+            #[test]
+            fn watch_closes_on_receivers_drop() {
+                let (sender, receiver) = watch::channel(0);
+                let other = receiver.clone();
+                let mut closed = Box::pin(sender.closed());
+                drop(receiver);
+                assert!(poll_once(closed.as_mut()).is_pending());
+                assert!(!sender.is_closed());
+                drop(other);
+                assert!(sender.is_closed());
+                assert!(poll_once(closed.as_mut()).is_ready());
+                assert_eq!(sender.send(1), Err(watch::SendError(1)));
+                assert_eq!(sender.send_replace(2), 0);
+                assert_eq!(*sender.borrow(), 2);
+            }
+
+            #[test]
+            fn rwlock_readers_share() {
+                let lock = RwLock::new(1);
+                let mut first = Box::pin(lock.read());
+                let mut second = Box::pin(lock.read());
+                assert_eq!(*ready(first.as_mut()), 1);
+                assert_eq!(*ready(second.as_mut()), 1);
+            }
+
+            #[test]
+            fn rwlock_writer_excludes_everyone() {
+                let lock = RwLock::new(0);
+                let mut write = Box::pin(lock.write());
+                let _guard = ready(write.as_mut());
+                let mut read = Box::pin(lock.read());
+                let mut second_write = Box::pin(lock.write());
+                assert!(poll_once(read.as_mut()).is_pending());
+                assert!(poll_once(second_write.as_mut()).is_pending());
+            }
+
+            #[test]
+            fn rwlock_queued_writer_blocks_new_readers() {
+                let lock = RwLock::new(0);
+                let read = lock_read(&lock);
+                let mut write = Box::pin(lock.write());
+                assert!(poll_once(write.as_mut()).is_pending());
+                let mut late_read = Box::pin(lock.read());
+                assert!(poll_once(late_read.as_mut()).is_pending());
+
+                drop(read);
+                let mut guard = ready(write.as_mut());
+                *guard = 1;
+                assert!(poll_once(late_read.as_mut()).is_pending());
+                drop(guard);
+                assert_eq!(*ready(late_read.as_mut()), 1);
+            }
+
+            #[test]
+            fn rwlock_waiters_are_served_in_order() {
+                let lock = RwLock::new(0);
+                let mut write = Box::pin(lock.write());
+                let guard = ready(write.as_mut());
+                let mut first_read = Box::pin(lock.read());
+                let mut second_write = Box::pin(lock.write());
+                let mut last_read = Box::pin(lock.read());
+                assert!(poll_once(first_read.as_mut()).is_pending());
+                assert!(poll_once(second_write.as_mut()).is_pending());
+                assert!(poll_once(last_read.as_mut()).is_pending());
+
+                drop(guard);
+                let read = ready(first_read.as_mut());
+                assert!(poll_once(second_write.as_mut()).is_pending());
+                assert!(poll_once(last_read.as_mut()).is_pending());
+
+                drop(read);
+                let mut guard = ready(second_write.as_mut());
+                *guard = 2;
+                assert!(poll_once(last_read.as_mut()).is_pending());
+
+                drop(guard);
+                assert_eq!(*ready(last_read.as_mut()), 2);
+            }
+
+            #[test]
+            fn rwlock_releasing_writer_wakes_all_queued_readers() {
+                let lock = RwLock::new(0);
+                let mut write = Box::pin(lock.write());
+                let guard = ready(write.as_mut());
+                let mut readers: Vec<_> = (0..3).map(|_| Box::pin(lock.read())).collect();
+                for reader in &mut readers {
+                    assert!(poll_once(reader.as_mut()).is_pending());
+                }
+                drop(guard);
+                for reader in &mut readers {
+                    assert_eq!(*ready(reader.as_mut()), 0);
+                }
+            }
+
+            #[test]
+            fn rwlock_dropped_writer_unblocks_readers() {
+                let lock = RwLock::new(0);
+                let read = lock_read(&lock);
+                let mut write = Box::pin(lock.write());
+                assert!(poll_once(write.as_mut()).is_pending());
+                let mut late_read = Box::pin(lock.read());
+                assert!(poll_once(late_read.as_mut()).is_pending());
+                drop(write);
+                assert_eq!(*ready(late_read.as_mut()), 0);
+                drop(read);
+            }
+
+            #[test]
+            fn rwlock_try_lock() {
+                let lock = RwLock::new(0);
+                let read = lock.try_read().unwrap();
+                assert!(lock.try_read().is_ok());
+                assert!(lock.try_write().is_err());
+                drop(read);
+                let mut write = lock.try_write().unwrap();
+                *write = 1;
+                assert!(lock.try_read().is_err());
+                assert!(lock.try_write().is_err());
+                drop(write);
+                assert_eq!(*lock.try_read().unwrap(), 1);
+            }
+
+            #[test]
+            fn rwlock_downgrade_admits_readers_only() {
+                let lock = RwLock::new(0);
+                let mut write = lock.try_write().unwrap();
+                *write = 1;
+                let mut read = Box::pin(lock.read());
+                assert!(poll_once(read.as_mut()).is_pending());
+                let downgraded = RwLockWriteGuard::downgrade(write);
+                assert_eq!(*ready(read.as_mut()), 1);
+                assert!(lock.try_write().is_err());
+                drop(downgraded);
+                assert!(lock.try_write().is_ok());
+            }
+
+            #[test]
+            fn rwlock_map_projects_guards() {
+                let lock = RwLock::new((1, String::from("a")));
+                let write = lock.try_write().unwrap();
+                let mut text = RwLockWriteGuard::map(write, |value| &mut value.1);
+                text.push('b');
+                assert!(lock.try_read().is_err());
+                drop(text);
+                let number = RwLockReadGuard::map(lock.try_read().unwrap(), |value| &value.0);
+                assert_eq!(*number, 1);
+                let Ok(text) = RwLockReadGuard::try_map(lock.try_read().unwrap(), |value| {
+                    Some(value.1.as_str())
+                }) else {
+                    panic!("mapping should succeed");
+                };
+                assert_eq!(&*text, "ab");
+                drop((number, text));
+                let write = lock.try_write().unwrap();
+                let Err(write) = RwLockWriteGuard::try_map(write, |_| None::<&mut i32>) else {
+                    panic!("mapping should fail");
+                };
+                assert!(lock.try_read().is_err());
+                drop(write);
+                assert!(lock.try_read().is_ok());
+            }
+
+            #[test]
+            fn rwlock_owned_guards_keep_lock_alive() {
+                let lock = std::sync::Arc::new(RwLock::new(vec![1, 2]));
+                let mut write = Box::pin(lock.clone().write_owned());
+                let write = ready(write.as_mut());
+                assert!(lock.try_read().is_err());
+                let mut first = OwnedRwLockWriteGuard::map(write, |values| &mut values[0]);
+                *first = 3;
+                drop(first);
+                let mut read = Box::pin(lock.clone().read_owned());
+                let read = ready(read.as_mut());
+                let second = OwnedRwLockReadGuard::map(read, |values| &values[1]);
+                assert!(lock.try_write().is_err());
+                assert_eq!(*second, 2);
+                drop(second);
+                assert_eq!(*lock.try_read().unwrap(), [3, 2]);
+            }
+
+            #[test]
+            fn mutex_hands_lock_over_in_order() {
+                let mutex = Mutex::new(vec![]);
+                let guard = mutex.try_lock().unwrap();
+                let mut waiters: Vec<_> = (0..3).map(|_| Box::pin(mutex.lock())).collect();
+                for waiter in &mut waiters {
+                    assert!(poll_once(waiter.as_mut()).is_pending());
+                }
+                drop(guard);
+                assert!(poll_once(waiters[2].as_mut()).is_pending());
+                assert!(poll_once(waiters[1].as_mut()).is_pending());
+                for (index, waiter) in waiters.iter_mut().enumerate() {
+                    ready(waiter.as_mut()).push(index);
+                }
+                assert_eq!(*mutex.try_lock().unwrap(), [0, 1, 2]);
+            }
+
+            #[test]
+            fn mutex_try_lock_while_contended() {
+                let mutex = Mutex::new(0);
+                let guard = mutex.try_lock().unwrap();
+                assert_eq!(
+                    mutex.try_lock().err().map(|error| error.to_string()),
+                    Some("operation would block".to_string())
+                );
+                let mut waiter = Box::pin(mutex.lock());
+                assert!(poll_once(waiter.as_mut()).is_pending());
+                // the lock goes straight to the queued waiter, so nobody can barge in
+                drop(guard);
+                assert!(mutex.try_lock().is_err());
+                // and a waiter dropped after being handed the lock releases it
+                drop(waiter);
+                assert!(mutex.try_lock().is_ok());
+            }
+
+            #[test]
+            fn mutex_owned_guards_keep_lock_alive() {
+                let mutex = std::sync::Arc::new(Mutex::new(1));
+                let mut guard = mutex.clone().try_lock_owned().unwrap();
+                assert!(std::sync::Arc::ptr_eq(guard.mutex(), &mutex));
+                *guard = 2;
+                assert!(mutex.try_lock().is_err());
+                assert!(mutex.clone().try_lock_owned().is_err());
+                let mut waiter = Box::pin(mutex.clone().lock_owned());
+                assert!(poll_once(waiter.as_mut()).is_pending());
+                drop(guard);
+                let guard = ready(waiter.as_mut());
+                assert_eq!(*guard, 2);
+                drop((waiter, guard));
+                assert_eq!(std::sync::Arc::into_inner(mutex).unwrap().into_inner(), 2);
+            }
+
+            #[test]
+            fn semaphore_acquire_many_waits_for_all_permits() {
+                let semaphore = Semaphore::new(2);
+                let mut acquire = Box::pin(semaphore.acquire_many(3));
+                assert!(poll_once(acquire.as_mut()).is_pending());
+                // a queued acquire holds on to what it could already take
+                assert_eq!(semaphore.available_permits(), 0);
+                semaphore.add_permits(1);
+                let permit = ready(acquire.as_mut()).unwrap();
+                assert_eq!(permit.num_permits(), 3);
+                drop(permit);
+                assert_eq!(semaphore.available_permits(), 3);
+            }
+
+            #[test]
+            fn semaphore_serves_waiters_in_order() {
+                let semaphore = Semaphore::new(0);
+                let mut large = Box::pin(semaphore.acquire_many(2));
+                let mut small = Box::pin(semaphore.acquire());
+                assert!(poll_once(large.as_mut()).is_pending());
+                assert!(poll_once(small.as_mut()).is_pending());
+                semaphore.add_permits(1);
+                assert!(poll_once(large.as_mut()).is_pending());
+                assert!(poll_once(small.as_mut()).is_pending());
+                semaphore.add_permits(1);
+                let large = ready(large.as_mut()).unwrap();
+                assert!(poll_once(small.as_mut()).is_pending());
+                drop(large);
+                assert_eq!(ready(small.as_mut()).unwrap().num_permits(), 1);
+                assert_eq!(semaphore.available_permits(), 2);
+            }
+
+            #[test]
+            fn semaphore_dropped_acquire_gives_permits_back() {
+                let semaphore = Semaphore::new(1);
+                let mut acquire = Box::pin(semaphore.acquire_many(2));
+                assert!(poll_once(acquire.as_mut()).is_pending());
+                assert_eq!(semaphore.available_permits(), 0);
+                drop(acquire);
+                assert_eq!(semaphore.available_permits(), 1);
+            }
+
+            #[test]
+            fn semaphore_try_acquire() {
+                let semaphore = Semaphore::new(2);
+                assert_eq!(
+                    semaphore.try_acquire_many(3).err(),
+                    Some(TryAcquireError::NoPermits)
+                );
+                let permit = semaphore.try_acquire().unwrap();
+                assert_eq!(permit.num_permits(), 1);
+                assert_eq!(semaphore.available_permits(), 1);
+                let held = semaphore.try_acquire_many(1).unwrap();
+                assert_eq!(
+                    semaphore.try_acquire().err(),
+                    Some(TryAcquireError::NoPermits)
+                );
+                drop(held);
+                permit.forget();
+                assert_eq!(semaphore.available_permits(), 1);
+            }
+
+            #[test]
+            #[should_panic(expected = "would overflow MAX_PERMITS")]
+            fn semaphore_add_permits_past_max_panics() {
+                let semaphore = Semaphore::new(Semaphore::MAX_PERMITS);
+                semaphore.add_permits(1);
+            }
+
+            #[test]
+            fn semaphore_add_permits_up_to_max() {
+                let semaphore = Semaphore::new(Semaphore::MAX_PERMITS - 1);
+                semaphore.add_permits(1);
+                assert_eq!(semaphore.available_permits(), Semaphore::MAX_PERMITS);
+            }
+
+            #[test]
+            fn semaphore_close_fails_acquires() {
+                let semaphore = Semaphore::new(0);
+                let mut waiting = Box::pin(semaphore.acquire());
+                assert!(poll_once(waiting.as_mut()).is_pending());
+                semaphore.close();
+                assert!(semaphore.is_closed());
+                let error: Option<AcquireError> = ready(waiting.as_mut()).err();
+                assert_eq!(error.unwrap().to_string(), "semaphore closed");
+                assert!(ready(Box::pin(semaphore.acquire_many(0)).as_mut()).is_err());
+                assert_eq!(semaphore.try_acquire().err(), Some(TryAcquireError::Closed));
+                // permits handed back after closing still count
+                semaphore.add_permits(1);
+                assert_eq!(semaphore.available_permits(), 1);
+                assert_eq!(semaphore.try_acquire().err(), Some(TryAcquireError::Closed));
+            }
+
+            #[test]
+            fn semaphore_owned_permits() {
+                let semaphore = std::sync::Arc::new(Semaphore::new(3));
+                let first = semaphore.clone().try_acquire_many_owned(2).unwrap();
+                assert_eq!(first.num_permits(), 2);
+                assert!(std::sync::Arc::ptr_eq(first.semaphore(), &semaphore));
+                assert_eq!(semaphore.available_permits(), 1);
+                let mut waiting = Box::pin(semaphore.clone().acquire_many_owned(2));
+                assert!(poll_once(waiting.as_mut()).is_pending());
+                assert_eq!(semaphore.available_permits(), 0);
+                // dropping an owned permit hands its permits to the queue first
+                drop(first);
+                let second = ready(waiting.as_mut()).unwrap();
+                assert_eq!(second.num_permits(), 2);
+                assert_eq!(semaphore.available_permits(), 1);
+                second.forget();
+                assert_eq!(semaphore.available_permits(), 1);
+                let third = ready(Box::pin(semaphore.clone().acquire_owned()).as_mut()).unwrap();
+                assert_eq!(third.num_permits(), 1);
+                assert_eq!(
+                    semaphore.clone().try_acquire_owned().err().map(|error| error.to_string()),
+                    Some("no permits available".to_string())
+                );
+                drop(third);
+                assert_eq!(semaphore.available_permits(), 1);
+                semaphore.close();
+                assert_eq!(
+                    semaphore.clone().try_acquire_owned().err(),
+                    Some(TryAcquireError::Closed)
+                );
+                assert!(ready(Box::pin(semaphore.clone().acquire_owned()).as_mut()).is_err());
+            }
+
+            #[test]
+            fn once_cell_waits_for_first_initializer() {
+                let cell = OnceCell::new();
+                let (sender, receiver) = oneshot::channel();
+                let mut first = Box::pin(cell.get_or_init(|| async { receiver.await.unwrap() }));
+                let mut second = Box::pin(cell.get_or_init(|| async { 2 }));
+                assert!(poll_once(first.as_mut()).is_pending());
+                assert!(poll_once(second.as_mut()).is_pending());
+                assert_eq!(cell.set(3), Err(SetError::InitializingError(3)));
+                sender.send(1).unwrap();
+                assert_eq!(*ready(first.as_mut()), 1);
+                assert_eq!(*ready(second.as_mut()), 1);
+                assert_eq!(cell.get(), Some(&1));
+            }
+
+            #[test]
+            fn once_cell_failed_initializer_hands_over() {
+                let cell = OnceCell::new();
+                let (sender, receiver) = oneshot::channel::<()>();
+                let mut first = Box::pin(cell.get_or_try_init(|| async {
+                    receiver.await.unwrap();
+                    Err("failed")
+                }));
+                let mut second = Box::pin(cell.get_or_try_init(|| async { Ok::<_, &str>(2) }));
+                assert!(poll_once(first.as_mut()).is_pending());
+                assert!(poll_once(second.as_mut()).is_pending());
+                sender.send(()).unwrap();
+                assert_eq!(ready(first.as_mut()), Err("failed"));
+                assert_eq!(ready(second.as_mut()), Ok(&2));
+            }
+
+            #[test]
+            fn once_cell_set_only_once() {
+                let cell = OnceCell::new();
+                assert!(!cell.initialized());
+                assert_eq!(cell.set(1), Ok(()));
+                assert_eq!(cell.set(2), Err(SetError::AlreadyInitializedError(2)));
+                assert_eq!(*ready(Box::pin(cell.get_or_init(|| async { 3 })).as_mut()), 1);
+                assert_eq!(cell.into_inner(), Some(1));
+            }
+
+            fn lock_read<T>(lock: &RwLock<T>) -> impl std::ops::Deref<Target = T> + '_ {
+                let mut read = Box::pin(lock.read());
+                ready(read.as_mut())
+            }
+        }
+    };
+}
+
+conformance!(native, tokasm::sync);
+
+#[cfg(feature = "wasm-sim")]
+conformance!(sim, tokasm::sync::sim);
@@ -0,0 +1,115 @@
+// Like conformance.rs, these run against both backends when `wasm-sim` is on.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+fn poll_once<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(Waker::noop()))
+}
+
+macro_rules! coordination {
+    ($backend:ident, $($sync:tt)*) => {
+        mod $backend {
+            use std::{pin::pin, task::Poll};
+
+            use super::poll_once;
+            use $($sync)*::{Barrier, WaitGroup};
+
+            #[test]
+            fn barrier_releases_all_with_one_leader() {
+                let barrier = Barrier::new(3);
+                let mut first = Box::pin(barrier.wait());
+                let mut second = Box::pin(barrier.wait());
+                assert!(poll_once(first.as_mut()).is_pending());
+                assert!(poll_once(second.as_mut()).is_pending());
+                let Poll::Ready(last) = poll_once(pin!(barrier.wait())) else {
+                    panic!("last task should not wait");
+                };
+                assert!(last.is_leader());
+                let Poll::Ready(first) = poll_once(first.as_mut()) else {
+                    panic!("barrier did not release");
+                };
+                let Poll::Ready(second) = poll_once(second.as_mut()) else {
+                    panic!("barrier did not release");
+                };
+                assert!(!first.is_leader());
+                assert!(!second.is_leader());
+            }
+
+            #[test]
+            fn barrier_is_reusable() {
+                let barrier = Barrier::new(2);
+                for _ in 0..3 {
+                    let mut waiter = Box::pin(barrier.wait());
+                    assert!(poll_once(waiter.as_mut()).is_pending());
+                    assert!(poll_once(pin!(barrier.wait())).is_ready());
+                    assert!(poll_once(waiter.as_mut()).is_ready());
+                }
+            }
+
+            #[test]
+            fn barrier_of_zero_never_waits() {
+                let barrier = Barrier::new(0);
+                let Poll::Ready(result) = poll_once(pin!(barrier.wait())) else {
+                    panic!("barrier of zero should not wait");
+                };
+                assert!(result.is_leader());
+            }
+
+            #[test]
+            fn barrier_dropped_wait_does_not_count() {
+                let barrier = Barrier::new(2);
+                let mut dropped = Box::pin(barrier.wait());
+                assert!(poll_once(dropped.as_mut()).is_pending());
+                drop(dropped);
+                let mut waiter = Box::pin(barrier.wait());
+                assert!(poll_once(waiter.as_mut()).is_pending());
+                let Poll::Ready(last) = poll_once(pin!(barrier.wait())) else {
+                    panic!("barrier did not release");
+                };
+                assert!(last.is_leader());
+                assert!(poll_once(waiter.as_mut()).is_ready());
+            }
+
+            #[test]
+            fn barrier_dropped_after_release_keeps_next_round() {
+                let barrier = Barrier::new(2);
+                let mut released = Box::pin(barrier.wait());
+                assert!(poll_once(released.as_mut()).is_pending());
+                assert!(poll_once(pin!(barrier.wait())).is_ready());
+                drop(released);
+                let mut waiter = Box::pin(barrier.wait());
+                assert!(poll_once(waiter.as_mut()).is_pending());
+                assert!(poll_once(pin!(barrier.wait())).is_ready());
+                assert!(poll_once(waiter.as_mut()).is_ready());
+            }
+
+            #[test]
+            fn wait_group_waits_for_every_done() {
+                let group = WaitGroup::new();
+                assert!(poll_once(pin!(group.wait())).is_ready());
+                group.add(2);
+                let mut wait = Box::pin(group.wait());
+                assert!(poll_once(wait.as_mut()).is_pending());
+                group.done();
+                assert!(poll_once(wait.as_mut()).is_pending());
+                group.done();
+                assert!(poll_once(wait.as_mut()).is_ready());
+            }
+
+            #[test]
+            #[should_panic]
+            fn wait_group_done_without_add() {
+                WaitGroup::new().done();
+            }
+        }
+    };
+}
+
+coordination!(native, tokasm::sync);
+
+#[cfg(feature = "wasm-sim")]
+coordination!(sim, tokasm::sync::sim);
@@ -0,0 +1,48 @@
+// `set_frame_rate` changes the frame period for the whole process, so it gets
+// a test binary of its own
+#![cfg(all(not(target_arch = "wasm32"), feature = "test-util"))]
+
+use std::{future::Future, sync::Mutex, time::Duration};
+
+use tokasm::time::{animation_frame, set_frame_rate};
+
+// tests that change the frame rate take turns
+static FRAME_RATE: Mutex<()> = Mutex::new(());
+
+fn run<F: Future>(future: F) -> F::Output {
+    let _frame_rate = FRAME_RATE.lock().unwrap_or_else(|error| error.into_inner());
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[test]
+fn set_frame_rate_changes_the_frame_period() {
+    run(async {
+        set_frame_rate(20);
+        let first = animation_frame().await;
+        let second = animation_frame().await;
+        assert_eq!(second.duration_since(first), Duration::from_millis(50));
+    });
+}
+
+#[test]
+fn set_frame_rate_keeps_the_period_above_zero() {
+    run(async {
+        set_frame_rate(u32::MAX);
+        let first = animation_frame().await;
+        let second = animation_frame().await;
+        // tokio's timers only have millisecond precision
+        assert!(second > first);
+        assert!(second.duration_since(first) <= Duration::from_millis(1));
+    });
+}
+
+#[test]
+#[should_panic = "must be non-zero"]
+fn set_frame_rate_rejects_zero() {
+    set_frame_rate(0);
+}
@@ -0,0 +1,863 @@
+// Runs the time utilities against Tokio's paused clock, and the WASM clock
+// on `tokasm::sim`'s event loop, so every sleep completes exactly at its
+// deadline without waiting in real time.
+#![cfg(all(not(target_arch = "wasm32"), feature = "test-util"))]
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Wake, Waker},
+    time::Duration,
+};
+
+use tokasm::{
+    sync::mpsc,
+    time::{
+        animation_frame, animation_frames, debounce, retry, retry_if,
+        schedule::{self, Schedule},
+        sleep_until, throttle, DelayQueue, Instant, RetryPolicy, SystemTime, UNIX_EPOCH,
+    },
+};
+
+// tasks spawned with `tokasm::spawn` run on this runtime too, so they follow
+// its clock
+fn run<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap()
+        .block_on(async {
+            let _runtime = tokasm::enter_test_runtime();
+            future.await
+        })
+}
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+// sends each item at its offset from now, then closes the channel at `end`
+fn timed<T: Send + 'static>(items: Vec<(u64, T)>, end: u64) -> mpsc::UnboundedReceiver<T> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let start = Instant::now();
+        for (at, item) in items {
+            sleep_until(start + ms(at)).await;
+            sender.send(item).unwrap();
+        }
+        sleep_until(start + ms(end)).await;
+    });
+    receiver
+}
+
+// the WASM clock runs on `tokasm::sim`'s event loop, frozen like Tokio's is
+// by `start_paused`
+#[cfg(feature = "wasm-sim")]
+fn run_sim<F: Future + 'static>(future: F) -> F::Output {
+    tokasm::sim::block_on(async {
+        tokasm::time::sim::pause();
+        let output = future.await;
+        tokasm::time::sim::resume();
+        output
+    })
+}
+
+macro_rules! interval_conformance {
+    ($backend:ident, $run:ident, $($time:tt)*) => {
+        mod $backend {
+            use super::{ms, $run as run};
+            use tokasm::time::MissedTickBehavior;
+            use $($time)*::{interval, sleep, Instant};
+
+            #[test]
+            fn interval_ticks_immediately_then_every_period() {
+                run(async {
+                    let start = Instant::now();
+                    let mut interval = interval(ms(10));
+                    assert_eq!(interval.missed_tick_behavior(), MissedTickBehavior::Burst);
+                    for expected in [0, 10, 20] {
+                        assert_eq!(interval.tick().await - start, ms(expected));
+                        assert_eq!(start.elapsed(), ms(expected));
+                    }
+                });
+            }
+
+            #[test]
+            fn interval_reset_restarts_period() {
+                run(async {
+                    let start = Instant::now();
+                    let mut interval = interval(ms(10));
+                    interval.tick().await;
+                    sleep(ms(5)).await;
+                    interval.reset();
+                    assert_eq!(interval.tick().await - start, ms(15));
+                });
+            }
+
+            #[test]
+            fn interval_burst_catches_up() {
+                run(async {
+                    let start = Instant::now();
+                    let mut interval = interval(ms(10));
+                    interval.tick().await;
+                    sleep(ms(35)).await;
+                    for expected in [10, 20, 30] {
+                        assert_eq!(interval.tick().await - start, ms(expected));
+                    }
+                    assert_eq!(start.elapsed(), ms(35));
+                    assert_eq!(interval.tick().await - start, ms(40));
+                    assert_eq!(start.elapsed(), ms(40));
+                });
+            }
+
+            #[test]
+            fn interval_delay_restarts_from_late_tick() {
+                run(async {
+                    let start = Instant::now();
+                    let mut interval = interval(ms(10));
+                    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    interval.tick().await;
+                    sleep(ms(35)).await;
+                    assert_eq!(interval.tick().await - start, ms(10));
+                    assert_eq!(start.elapsed(), ms(35));
+                    for expected in [45, 55] {
+                        assert_eq!(interval.tick().await - start, ms(expected));
+                        assert_eq!(start.elapsed(), ms(expected));
+                    }
+                });
+            }
+
+            #[test]
+            fn interval_skip_keeps_schedule() {
+                run(async {
+                    let start = Instant::now();
+                    let mut interval = interval(ms(10));
+                    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    interval.tick().await;
+                    sleep(ms(35)).await;
+                    assert_eq!(interval.tick().await - start, ms(10));
+                    assert_eq!(start.elapsed(), ms(35));
+                    for expected in [40, 50] {
+                        assert_eq!(interval.tick().await - start, ms(expected));
+                        assert_eq!(start.elapsed(), ms(expected));
+                    }
+                });
+            }
+        }
+    };
+}
+
+interval_conformance!(native_interval, run, tokasm::time);
+
+#[cfg(feature = "wasm-sim")]
+interval_conformance!(sim_interval, run_sim, tokasm::time::sim);
+
+#[test]
+fn delay_queue_expires_in_deadline_order() {
+    run(async {
+        let start = Instant::now();
+        let mut queue = DelayQueue::new();
+        queue.insert('c', ms(30));
+        queue.insert('a', ms(10));
+        queue.insert('b', ms(20));
+        for (value, elapsed) in [('a', 10), ('b', 20), ('c', 30)] {
+            let expired = queue.next_expired().await.unwrap();
+            assert_eq!(*expired.get_ref(), value);
+            assert_eq!(expired.deadline() - start, ms(elapsed));
+            assert_eq!(start.elapsed(), ms(elapsed));
+        }
+        assert!(queue.is_empty());
+        assert!(queue.next_expired().await.is_none());
+    });
+}
+
+#[test]
+fn delay_queue_reset_moves_expiry() {
+    run(async {
+        let start = Instant::now();
+        let mut queue = DelayQueue::new();
+        let a = queue.insert('a', ms(10));
+        queue.insert('b', ms(20));
+        assert!(queue.reset(&a, ms(30)));
+        assert_eq!(queue.deadline(&a), Some(start + ms(30)));
+        assert_eq!(queue.next_expired().await.unwrap().into_inner(), 'b');
+        let expired = queue.next_expired().await.unwrap();
+        assert_eq!(expired.key(), a);
+        assert_eq!(start.elapsed(), ms(30));
+        assert!(!queue.reset(&a, ms(10)));
+    });
+}
+
+#[test]
+fn delay_queue_remove_skips_entry() {
+    run(async {
+        let start = Instant::now();
+        let mut queue = DelayQueue::new();
+        let a = queue.insert('a', ms(10));
+        queue.insert('b', ms(20));
+        assert_eq!(queue.remove(&a), Some('a'));
+        assert_eq!(queue.remove(&a), None);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.next_expired().await.unwrap().into_inner(), 'b');
+        assert_eq!(start.elapsed(), ms(20));
+    });
+}
+
+#[test]
+fn delay_queue_earlier_insert_replaces_timer() {
+    run(async {
+        let start = Instant::now();
+        let mut queue = DelayQueue::new();
+        queue.insert('a', ms(50));
+        // start waiting on the first entry's timer
+        tokio::select! {
+            _ = queue.next_expired() => panic!("nothing has expired yet"),
+            _ = tokasm::time::sleep(ms(5)) => {}
+        }
+        queue.insert('b', ms(10));
+        assert_eq!(queue.next_expired().await.unwrap().into_inner(), 'b');
+        assert_eq!(start.elapsed(), ms(15));
+        assert_eq!(queue.next_expired().await.unwrap().into_inner(), 'a');
+        assert_eq!(start.elapsed(), ms(50));
+    });
+}
+
+struct WakeFlag(AtomicBool);
+
+impl Wake for WakeFlag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+// another task may insert while the queue is being polled, so the poll's
+// timer has to be re-targeted without waiting for the later deadline
+#[test]
+fn delay_queue_earlier_insert_wakes_pending_poll() {
+    run(async {
+        let mut queue = DelayQueue::new();
+        queue.insert('a', ms(50));
+        let woken = Arc::new(WakeFlag(AtomicBool::new(false)));
+        let waker = Waker::from(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert!(queue.poll_expired(&mut cx).is_pending());
+        queue.insert('b', ms(60));
+        assert!(!woken.0.load(Ordering::SeqCst));
+        queue.insert('c', ms(10));
+        assert!(woken.0.load(Ordering::SeqCst));
+        woken.0.store(false, Ordering::SeqCst);
+        assert!(queue.poll_expired(&mut cx).is_pending());
+        let b = queue.insert('b', ms(60));
+        assert!(queue.reset(&b, ms(5)));
+        assert!(woken.0.load(Ordering::SeqCst));
+        woken.0.store(false, Ordering::SeqCst);
+        assert!(queue.poll_expired(&mut cx).is_pending());
+        queue.clear();
+        assert!(woken.0.load(Ordering::SeqCst));
+        assert!(queue.poll_expired(&mut cx).is_ready());
+    });
+}
+
+#[test]
+fn debounce_yields_after_quiet_period() {
+    run(async {
+        let start = Instant::now();
+        let mut debounced = debounce(
+            timed(vec![(0, 'a'), (5, 'b'), (30, 'c'), (35, 'd')], 80),
+            ms(10),
+        );
+        for (item, elapsed) in [(Some('b'), 15), (Some('d'), 45), (None, 80)] {
+            assert_eq!(debounced.next().await, item);
+            assert_eq!(start.elapsed(), ms(elapsed));
+        }
+    });
+}
+
+#[test]
+fn debounce_flushes_when_stream_ends() {
+    run(async {
+        let start = Instant::now();
+        let mut debounced = debounce(timed(vec![(0, 'a')], 5), ms(10));
+        assert_eq!(debounced.next().await, Some('a'));
+        assert_eq!(start.elapsed(), ms(5));
+        assert_eq!(debounced.next().await, None);
+    });
+}
+
+#[test]
+fn throttle_yields_latest_once_per_period() {
+    run(async {
+        let start = Instant::now();
+        let items = vec![(0, 'a'), (2, 'b'), (4, 'c'), (25, 'd'), (40, 'e')];
+        let mut throttled = throttle(timed(items, 60), ms(10));
+        for (item, elapsed) in [('a', 0), ('c', 10), ('d', 25), ('e', 40)] {
+            assert_eq!(throttled.next().await, Some(item));
+            assert_eq!(start.elapsed(), ms(elapsed));
+        }
+        assert_eq!(throttled.next().await, None);
+        assert_eq!(start.elapsed(), ms(60));
+    });
+}
+
+#[test]
+fn throttle_keeps_rate_when_stream_ends() {
+    run(async {
+        let start = Instant::now();
+        let mut throttled = throttle(timed(vec![(0, 'a'), (2, 'b')], 3), ms(10));
+        assert_eq!(throttled.next().await, Some('a'));
+        assert_eq!(throttled.next().await, Some('b'));
+        assert_eq!(start.elapsed(), ms(10));
+        assert_eq!(throttled.next().await, None);
+        assert_eq!(start.elapsed(), ms(10));
+    });
+}
+
+#[test]
+fn retry_backs_off_exponentially() {
+    run(async {
+        let start = Instant::now();
+        let mut attempts = vec![];
+        let policy = RetryPolicy::exponential(ms(10))
+            .max_delay(ms(50))
+            .max_attempts(6);
+        let result: Result<(), usize> = retry(policy, || {
+            attempts.push(start.elapsed());
+            let attempt = attempts.len();
+            async move { Err(attempt) }
+        })
+        .await;
+        assert_eq!(result, Err(6));
+        assert_eq!(attempts, [0, 10, 30, 70, 120, 170].map(ms));
+    });
+}
+
+#[test]
+fn retry_returns_first_success() {
+    run(async {
+        let start = Instant::now();
+        let mut attempts = 0;
+        let result: Result<usize, ()> = retry(RetryPolicy::exponential(ms(10)), || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt == 3 {
+                    Ok(attempt)
+                } else {
+                    Err(())
+                }
+            }
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+        assert_eq!(start.elapsed(), ms(30));
+    });
+}
+
+#[test]
+fn retry_gives_up_before_max_elapsed() {
+    run(async {
+        let start = Instant::now();
+        let mut attempts = 0;
+        let policy = RetryPolicy::exponential(ms(10)).max_elapsed(ms(35));
+        let result: Result<(), ()> = retry(policy, || {
+            attempts += 1;
+            async { Err(()) }
+        })
+        .await;
+        assert_eq!(result, Err(()));
+        // the third retry would have started at 70ms
+        assert_eq!(attempts, 3);
+        assert_eq!(start.elapsed(), ms(30));
+    });
+}
+
+#[test]
+fn retry_if_stops_on_permanent_error() {
+    run(async {
+        let start = Instant::now();
+        let mut errors = vec!["timeout", "not found", "timeout"].into_iter();
+        let result: Result<(), &str> = retry_if(
+            RetryPolicy::exponential(ms(10)),
+            || {
+                let error = errors.next().unwrap();
+                async move { Err(error) }
+            },
+            |error| *error == "timeout",
+        )
+        .await;
+        assert_eq!(result, Err("not found"));
+        assert_eq!(start.elapsed(), ms(10));
+    });
+}
+
+#[tokio::test]
+async fn spawned_sleep_waits_for_advance() {
+    let _runtime = tokasm::enter_test_runtime();
+    tokasm::time::pause();
+    let done = Arc::new(AtomicBool::new(false));
+    let sleep = sleep_until(Instant::now() + ms(1000));
+    tokasm::spawn({
+        let done = done.clone();
+        async move {
+            sleep.await;
+            done.store(true, Ordering::SeqCst);
+        }
+    });
+    tokasm::time::advance(ms(999)).await;
+    assert!(!done.load(Ordering::SeqCst));
+    // tokio rounds timers up to its next millisecond tick, which isn't
+    // aligned with the instant time was paused at
+    tokasm::time::advance(ms(2)).await;
+    assert!(done.load(Ordering::SeqCst));
+    tokasm::time::resume();
+}
+
+// without `enter_test_runtime`, tasks go to tokasm's multi-thread runtime even
+// when spawned from a current-thread one, so they outlive it
+#[test]
+fn spawned_tasks_outlive_the_runtime_they_came_from() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            tokasm::spawn(async move {
+                tokio::task::yield_now().await;
+                sender.send(()).unwrap();
+            });
+        });
+    receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+}
+
+// tasks spawned from outside a runtime go to tokasm's multi-thread one too
+#[test]
+fn blocking_send_works_in_spawned_tasks() {
+    let (sender, mut receiver) = mpsc::channel(1);
+    tokasm::spawn(async move {
+        sender.blocking_send(1).unwrap();
+        sender.blocking_send(2).unwrap();
+    });
+    let received = run(async { [receiver.recv().await, receiver.recv().await] });
+    assert_eq!(received, [Some(1), Some(2)]);
+}
+
+#[tokio::test]
+async fn system_time_follows_the_paused_clock() {
+    tokasm::time::pause();
+    let start = SystemTime::now();
+    tokasm::time::advance(Duration::from_secs(5)).await;
+    assert_eq!(
+        SystemTime::now().duration_since(start).unwrap(),
+        Duration::from_secs(5)
+    );
+    tokasm::time::resume();
+}
+
+// it reads the real wall clock until time is paused, and once resumed reads
+// it again, ahead by however far time was advanced
+#[tokio::test]
+async fn system_time_keeps_advanced_time_after_resuming() {
+    let before = std::time::SystemTime::now();
+    let now = std::time::SystemTime::from(SystemTime::now());
+    assert!(before <= now && now <= std::time::SystemTime::now());
+    tokasm::time::pause();
+    tokasm::time::advance(Duration::from_secs(3600)).await;
+    tokasm::time::resume();
+    let now = std::time::SystemTime::from(SystemTime::now());
+    let ahead = now.duration_since(std::time::SystemTime::now()).unwrap();
+    assert!(ahead <= Duration::from_secs(3600) && ahead > Duration::from_secs(3599));
+}
+
+#[tokio::test]
+async fn set_system_time_leaves_instant_alone() {
+    tokasm::time::pause();
+    let start = Instant::now();
+    let time = new_year(0);
+    tokasm::time::set_system_time(time);
+    tokasm::time::advance(Duration::from_secs(5)).await;
+    assert_eq!(SystemTime::now(), time + Duration::from_secs(5));
+    assert_eq!(start.elapsed(), Duration::from_secs(5));
+    tokasm::time::resume();
+}
+
+// runs `expression` on the paused clock, with the wall clock starting at
+// `start`, until `script` finishes. `on_run` gets the number of each run as
+// it happens. returns the wall time of every run
+fn scheduled_runs<F: Future>(
+    expression: &str,
+    start: SystemTime,
+    on_run: impl Fn(usize) + Send + 'static,
+    script: F,
+) -> Vec<SystemTime> {
+    let schedule = Schedule::parse(expression).unwrap();
+    let runs = Arc::new(Mutex::new(vec![]));
+    run(async {
+        tokasm::time::set_system_time(start);
+        tokio::spawn(schedule::run(schedule, {
+            let runs = runs.clone();
+            move || {
+                let mut runs = runs.lock().unwrap();
+                runs.push(SystemTime::now());
+                on_run(runs.len());
+                async {}
+            }
+        }));
+        script.await;
+    });
+    let runs = runs.lock().unwrap();
+    runs.clone()
+}
+
+// 2026-01-01 00:00 UTC
+fn new_year(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(20454 * 24 * 60 * 60 + seconds)
+}
+
+#[test]
+fn schedule_never_runs_a_minute_twice_when_the_clock_goes_back() {
+    let on_run = |run| {
+        if run == 1 {
+            tokasm::time::set_system_time(new_year(0) - Duration::from_secs(30));
+        }
+    };
+    let runs = scheduled_runs("* * * * *", new_year(30), on_run, async {
+        tokasm::time::sleep(Duration::from_secs(200)).await;
+    });
+    // 23:59 and 00:01 come around again, but only 00:02 is new
+    assert_eq!(runs, [new_year(60), new_year(120)]);
+}
+
+#[test]
+fn schedule_runs_missed_minutes_once_when_the_clock_jumps_ahead() {
+    let runs = scheduled_runs("* * * * *", new_year(30), |_| {}, async {
+        tokasm::time::sleep(Duration::from_secs(10)).await;
+        tokasm::time::set_system_time(new_year(645));
+        tokasm::time::sleep(Duration::from_secs(90)).await;
+    });
+    // 00:01 through 00:11 are missed, and make up a single run as soon as the
+    // pending sleep ends. 00:12 runs on time
+    assert_eq!(runs, [new_year(665), new_year(720)]);
+}
+
+#[test]
+fn schedule_notices_clock_changes_while_sleeping() {
+    let runs = scheduled_runs("0 * * * *", new_year(30), |_| {}, async {
+        tokasm::time::sleep(Duration::from_secs(10)).await;
+        tokasm::time::set_system_time(new_year(3580));
+        tokasm::time::sleep(Duration::from_secs(51)).await;
+    });
+    // 01:00 was an hour away, but the clock is checked again within a minute
+    assert_eq!(runs, [new_year(3630)]);
+}
+
+// these keep the default 60Hz, since `set_frame_rate` would change it for
+// every test in the process
+#[test]
+fn animation_frames_land_on_frame_boundaries() {
+    run(async {
+        let period = Duration::from_nanos(1_000_000_000 / 60);
+        let first = animation_frame().await;
+        assert!(first.instant() <= Instant::now());
+        let mut frames = animation_frames();
+        let second = frames.next().await;
+        assert_eq!(second.duration_since(first), period);
+        // a late caller waits for the next boundary rather than a full period
+        tokasm::time::sleep(ms(40)).await;
+        let third = frames.next().await;
+        assert_eq!(third.duration_since(second), period * 3);
+    });
+}
+
+#[test]
+fn tasks_awaiting_the_same_frame_share_its_timestamp() {
+    run(async {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        for _ in 0..3 {
+            let sender = sender.clone();
+            tokasm::spawn(async move {
+                sender.send(animation_frame().await).unwrap();
+            });
+        }
+        drop(sender);
+        let frame = animation_frame().await;
+        for _ in 0..3 {
+            assert_eq!(receiver.recv().await, Some(frame));
+        }
+        assert_eq!(receiver.recv().await, None);
+    });
+}
+
+#[cfg(not(feature = "idle"))]
+#[test]
+#[should_panic = "needs the `idle` feature"]
+fn idle_needs_the_idle_feature() {
+    run(tokasm::time::idle());
+}
+
+#[cfg(feature = "idle")]
+mod idle {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        task::Poll,
+        time::Duration,
+    };
+
+    use super::run;
+
+    // `idle` waits until every tokasm task is parked, so a task that's only
+    // between polls keeps it waiting. these spawn onto `run`'s current-thread
+    // runtime, which polls the test as soon as it's woken
+    #[test]
+    fn idle_waits_for_yielding_tasks() {
+        run(async {
+            let done = Arc::new(AtomicBool::new(false));
+            tokasm::spawn({
+                let done = done.clone();
+                async move {
+                    for _ in 0..50 {
+                        tokio::task::yield_now().await;
+                    }
+                    done.store(true, Ordering::SeqCst);
+                }
+            });
+            tokasm::time::idle().await;
+            assert!(done.load(Ordering::SeqCst));
+        });
+    }
+
+    // each task wakes the other before it parks, so one of them is always
+    // runnable until they're done. the one answering yields first, and on its
+    // second yield the other one has already parked, so then it's runnable
+    // only through tokio's deferred wakers. this breaks if tokio stops waking
+    // them all at once before polling other tasks
+    #[test]
+    fn idle_waits_for_tasks_waking_each_other() {
+        run(async {
+            let done = Arc::new(AtomicBool::new(false));
+            let (ping, mut pings) = tokasm::sync::mpsc::unbounded_channel();
+            let (pong, mut pongs) = tokasm::sync::mpsc::unbounded_channel();
+            tokasm::spawn(async move {
+                while let Some(round) = pings.recv().await {
+                    for _ in 0..2 {
+                        tokio::task::yield_now().await;
+                    }
+                    pong.send(round).unwrap();
+                }
+            });
+            tokasm::spawn({
+                let done = done.clone();
+                async move {
+                    for round in 0..50 {
+                        ping.send(round).unwrap();
+                        assert_eq!(pongs.recv().await, Some(round));
+                    }
+                    done.store(true, Ordering::SeqCst);
+                }
+            });
+            tokasm::time::idle().await;
+            assert!(done.load(Ordering::SeqCst));
+        });
+    }
+
+    #[test]
+    fn idle_resolves_while_tasks_sleep() {
+        let done = Arc::new(AtomicBool::new(false));
+        tokasm::spawn({
+            let done = done.clone();
+            async move {
+                tokasm::time::sleep(Duration::from_secs(3600)).await;
+                done.store(true, Ordering::SeqCst);
+            }
+        });
+        run(tokasm::time::idle());
+        assert!(!done.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn idle_waits_for_nested_spawns() {
+        run(async {
+            let finished = Arc::new(AtomicUsize::new(0));
+            tokasm::spawn({
+                let finished = finished.clone();
+                async move {
+                    tokio::task::yield_now().await;
+                    tokasm::spawn({
+                        let finished = finished.clone();
+                        async move {
+                            tokio::task::yield_now().await;
+                            tokasm::spawn({
+                                let finished = finished.clone();
+                                async move {
+                                    tokio::task::yield_now().await;
+                                    finished.fetch_add(1, Ordering::SeqCst);
+                                }
+                            });
+                            finished.fetch_add(1, Ordering::SeqCst);
+                        }
+                    });
+                    finished.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+            tokasm::time::idle().await;
+            assert_eq!(finished.load(Ordering::SeqCst), 3);
+        });
+    }
+
+    #[test]
+    fn idle_waits_for_tasks_woken_during_their_poll() {
+        run(async {
+            let done = Arc::new(AtomicBool::new(false));
+            tokasm::spawn({
+                let done = done.clone();
+                async move {
+                    let mut woken = false;
+                    std::future::poll_fn(|cx| {
+                        if woken {
+                            return Poll::Ready(());
+                        }
+                        woken = true;
+                        let waker = cx.waker().clone();
+                        std::thread::spawn(move || waker.wake()).join().unwrap();
+                        Poll::Pending
+                    })
+                    .await;
+                    done.store(true, Ordering::SeqCst);
+                }
+            });
+            tokasm::time::idle().await;
+            assert!(done.load(Ordering::SeqCst));
+        });
+    }
+
+    // a current-thread runtime drops the tasks it never got to when it shuts
+    // down, which mustn't leave them counted as active
+    #[test]
+    fn idle_ignores_tasks_dropped_before_finishing() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let _runtime = tokasm::enter_test_runtime();
+            tokasm::spawn(std::future::pending());
+        });
+        drop(runtime);
+        run(tokasm::time::idle());
+    }
+}
+
+#[cfg(feature = "wasm-sim")]
+mod sim {
+    use std::{cell::Cell, future::Future, rc::Rc, task::Poll, time::Duration};
+
+    use tokasm::{
+        sim::{block_on, pending_timers, spawn},
+        time::sim::{
+            advance, animation_frame, pause, resume, set_system_time, sleep, sleep_until, Instant,
+            SystemTime, UNIX_EPOCH,
+        },
+    };
+
+    use super::ms;
+
+    #[test]
+    fn spawned_sleep_waits_for_advance() {
+        block_on(async {
+            pause();
+            let done = Rc::new(Cell::new(false));
+            let sleep = sleep_until(Instant::now() + ms(1000));
+            spawn({
+                let done = done.clone();
+                async move {
+                    sleep.await;
+                    done.set(true);
+                }
+            });
+            advance(ms(999)).await;
+            assert!(!done.get());
+            advance(ms(2)).await;
+            assert!(done.get());
+            resume();
+        });
+    }
+
+    #[test]
+    fn animation_frame_follows_the_advanced_clock() {
+        block_on(async {
+            pause();
+            advance(Duration::from_secs(60)).await;
+            resume();
+            let before = Instant::now();
+            let frame = animation_frame().await;
+            assert!(before <= frame.instant() && frame.instant() <= Instant::now());
+        });
+    }
+
+    #[test]
+    fn sleep_uses_event_loop_timers() {
+        block_on(async {
+            let start = Instant::now();
+            sleep(ms(20)).await;
+            assert_eq!(start.elapsed(), ms(20));
+        });
+    }
+
+    #[test]
+    fn sleep_longer_than_a_timeout_can_wait() {
+        block_on(async {
+            let start = Instant::now();
+            let days = Duration::from_secs(25 * 24 * 60 * 60);
+            sleep(days).await;
+            assert_eq!(start.elapsed(), days);
+        });
+    }
+
+    #[test]
+    fn dropped_sleep_clears_timer() {
+        block_on(async {
+            let mut sleep = Box::pin(sleep(ms(20)));
+            let poll = std::future::poll_fn(|cx| Poll::Ready(sleep.as_mut().poll(cx))).await;
+            assert!(poll.is_pending());
+            assert_eq!(pending_timers(), 1);
+            drop(sleep);
+            assert_eq!(pending_timers(), 0);
+        });
+    }
+
+    #[test]
+    fn system_time_follows_the_paused_clock() {
+        block_on(async {
+            pause();
+            let start = SystemTime::now();
+            advance(Duration::from_secs(5)).await;
+            assert_eq!(
+                SystemTime::now().duration_since(start).unwrap(),
+                Duration::from_secs(5)
+            );
+            resume();
+        });
+    }
+
+    #[test]
+    fn set_system_time_leaves_instant_alone() {
+        block_on(async {
+            let start = Instant::now();
+            let time = UNIX_EPOCH + Duration::from_secs(1_000_000);
+            set_system_time(time);
+            sleep(Duration::from_secs(5)).await;
+            assert_eq!(SystemTime::now(), time + Duration::from_secs(5));
+            assert_eq!(start.elapsed(), Duration::from_secs(5));
+            pause();
+            set_system_time(time);
+            advance(Duration::from_secs(5)).await;
+            assert_eq!(SystemTime::now(), time + Duration::from_secs(5));
+            resume();
+        });
+    }
+}